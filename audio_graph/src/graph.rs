@@ -2,43 +2,97 @@
 use crate::module::Module;
 use crate::sample::{Frame, Sample};
 use fixedbitset::FixedBitSet;
-use petgraph::algo::{toposort, DfsSpace};
+use petgraph::algo::{tarjan_scc, toposort, DfsSpace};
 use petgraph::prelude::*;
+use petgraph::visit::NodeIndexable;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
 
 pub type Node = Box<Module + Send>;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// Connections form a cycle through the listed nodes.
+    Cycle(Vec<NodeIndex>),
+    /// Node doesn't have an input port with such name.
+    UnknownPort(NodeIndex, String),
+    /// Node has fewer inputs than sources being connected to it, the number of inputs is given.
+    TooManySources(NodeIndex, usize),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::Cycle(nodes) => write!(
+                f,
+                "Graph has a cycle through nodes {}.",
+                nodes
+                    .iter()
+                    .map(|idx| idx.index().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            GraphError::UnknownPort(idx, name) => {
+                write!(f, "Node {} has no input `{}`.", idx.index(), name)
+            }
+            GraphError::TooManySources(idx, inputs) => {
+                write!(f, "Node {} takes at most {} sources.", idx.index(), inputs)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
 /// Structure which manages network of Modules.
 pub struct AudioGraph {
     channels: usize,
     /// Nodes are boxed Modules and edges represent source->sink connections.
-    graph: StableGraph<Node, ()>,
+    /// Edge weight is the index of sink's input port, ref `Module::input_ports`.
+    graph: StableGraph<Node, usize>,
     /// `sample` writes output from source nodes into this buffer and then passes it to sink.
     /// Buffer is reused during graph traversal and between samples to avoid memory allocations.
+    /// It is sized for the node with the most sources in `update_order`.
     input: Vec<Sample>,
     /// `sample` walks graph in topological order which is cached here.
     order: Vec<NodeIndex>,
+    /// Structural position of each node, sorted by path. Ref `update_paths`.
+    paths: Vec<(Vec<usize>, NodeIndex)>,
     space: DfsSpace<NodeIndex, FixedBitSet>,
     parameters: usize,
+    /// Number of frames `process_block` computes at once, either BLOCK or 1. Ref
+    /// `Module::per_frame`.
+    block: usize,
+    /// `process_block` counterpart of nodes' outputs: interleaved frames of the current block,
+    /// indexed by node index.
+    blocks: Vec<Vec<Sample>>,
+    /// `process_block` counterpart of `input`.
+    block_input: Vec<Sample>,
+    /// External input of the current block, interleaved.
+    external: Vec<Sample>,
 }
 
-/// Maximum number of sources to connect to sink.
-/// This number is required because input buffer is allocated during AudioGraph initialization and
-/// then re-used across all nodes sampling. There is no real need to have it hardcoded though.
-/// It can be made an argument of AudioGraph::new.
-const MAX_SOURCES: usize = 16;
+/// Maximum number of frames `process_block` passes to modules at once.
+/// It bounds the size of per-node buffers while still amortising per-frame overhead.
+pub const BLOCK: usize = 64;
 
 impl AudioGraph {
     pub fn new(channels: usize, parameters: usize) -> Self {
         let graph = StableGraph::default();
         let space = DfsSpace::new(&graph);
-        let input_len = channels * MAX_SOURCES;
         AudioGraph {
             channels,
             graph,
-            input: vec![0.0; input_len],
+            input: vec![0.0; channels + parameters],
             order: Vec::new(),
+            paths: Vec::new(),
             space,
             parameters,
+            block: BLOCK,
+            blocks: Vec::new(),
+            block_input: Vec::new(),
+            external: vec![0.0; BLOCK * (channels + parameters)],
         }
     }
 
@@ -50,17 +104,12 @@ impl AudioGraph {
             let idx = *idx;
             let g = &mut self.graph;
             if g[idx].inputs() > 0 {
-                // NOTE neighbors_directed walks edges starting from the most recently added (is it
-                // guaranteed?). This is the reason why connection methods (connect, set_sources,
-                // chain etc.) call clear_sources first and reverse sources. Always resetting
-                // sources instead of finer-grained manipulation reduces risk of confusing their
-                // order. We might want to consider to name edges and pass HashMap instead instead
-                // of Vec as input. But it implies non-neglegible performance hit.
-                //
+                // Source output goes to the place of the port it is connected to.
                 // Ref `Module::sample` doc for an example of input layout.
-                for (i, source) in g.neighbors_directed(idx, Incoming).enumerate() {
-                    let offset = i * channels;
-                    self.input[offset..(offset + channels)].clone_from_slice(g[source].output());
+                for edge in g.edges_directed(idx, Incoming) {
+                    let offset = edge.weight() * channels;
+                    self.input[offset..(offset + channels)]
+                        .clone_from_slice(g[edge.source()].output());
                 }
             } else {
                 // If node does not have any inputs it might be waiting for external input and parameters.
@@ -78,6 +127,73 @@ impl AudioGraph {
         }
     }
 
+    /// Compute the next `frames` frames of AudioGraph's sound stream.
+    ///
+    /// `input` has a slice per external input (audio channels followed by parameters) and
+    /// `output` has a slice per channel, each at least `frames` long. It produces the same
+    /// result as calling `sample` for each frame, but calls modules' `process_block` on
+    /// blocks of up to BLOCK frames.
+    pub fn process_block(
+        &mut self,
+        input: &[&[Sample]],
+        output: &mut [&mut [Sample]],
+        frames: usize,
+    ) {
+        let channels = self.channels;
+        let width = channels + self.parameters;
+        let mut offset = 0;
+        while offset < frames {
+            let n = self.block.min(frames - offset);
+            // Interleave external input into frames expected by modules without inputs.
+            for (i, frame) in self.external[..n * width].chunks_mut(width).enumerate() {
+                for (x, source) in frame.iter_mut().zip(input) {
+                    *x = source[offset + i];
+                }
+            }
+            for idx in &self.order {
+                let idx = *idx;
+                let g = &mut self.graph;
+                let inputs = g[idx].inputs();
+                let node_input = if inputs > 0 {
+                    // Same layout as in `sample`, repeated for each frame.
+                    let stride = inputs * channels;
+                    for edge in g.edges_directed(idx, Incoming) {
+                        let block = &self.blocks[edge.source().index()];
+                        for frame in 0..n {
+                            let offset = frame * stride + edge.weight() * channels;
+                            self.block_input[offset..(offset + channels)].clone_from_slice(
+                                &block[(frame * channels)..((frame + 1) * channels)],
+                            );
+                        }
+                    }
+                    &self.block_input[..n * stride]
+                } else {
+                    &self.external[..n * width]
+                };
+                let node_output = &mut self.blocks[idx.index()][..n * channels];
+                g[idx].process_block(node_input, node_output, n);
+            }
+            match self.order.last() {
+                Some(idx) => {
+                    let block = &self.blocks[idx.index()];
+                    for (channel, output) in output.iter_mut().enumerate() {
+                        for frame in 0..n {
+                            output[offset + frame] = block[frame * channels + channel];
+                        }
+                    }
+                }
+                None => {
+                    for output in output.iter_mut() {
+                        for y in output[offset..(offset + n)].iter_mut() {
+                            *y = 0.0;
+                        }
+                    }
+                }
+            }
+            offset += n;
+        }
+    }
+
     pub fn node(&self, idx: NodeIndex) -> &Node {
         &self.graph[idx]
     }
@@ -90,53 +206,231 @@ impl AudioGraph {
 
     /// Connect nodes in a chain, from left to right.
     /// It clears nodes' sources before connecting, except for the first one.
-    pub fn chain(&mut self, nodes: &[NodeIndex]) {
+    pub fn chain(&mut self, nodes: &[NodeIndex]) -> Result<(), GraphError> {
+        for sink in nodes.iter().skip(1) {
+            self.check_sources(*sink, 1)?;
+        }
         for i in 0..(nodes.len() - 1) {
             self.clear_sources(nodes[i + 1]);
-            self.graph.update_edge(nodes[i], nodes[i + 1], ());
+            self.graph.update_edge(nodes[i], nodes[i + 1], 0);
         }
-        self.update_order();
+        self.update_order()
     }
 
     /// Set node `a` as a single source of node `b`.
     /// It clears `b`'s sources before connecting, to set multiple sources use `set_sources`.
-    pub fn connect(&mut self, a: NodeIndex, b: NodeIndex) {
+    pub fn connect(&mut self, a: NodeIndex, b: NodeIndex) -> Result<(), GraphError> {
+        self.check_sources(b, 1)?;
         self.clear_sources(b);
-        self.graph.update_edge(a, b, ());
-        self.update_order();
+        self.graph.update_edge(a, b, 0);
+        self.update_order()
+    }
+
+    /// Connect `source` to the input port of `sink` named `port`, replacing the source which was
+    /// connected to that port before. Other sources of `sink` are kept.
+    pub fn connect_port(
+        &mut self,
+        source: NodeIndex,
+        sink: NodeIndex,
+        port: &str,
+    ) -> Result<(), GraphError> {
+        let position = self.graph[sink]
+            .input_ports()
+            .iter()
+            .position(|p| p.name == port)
+            .ok_or_else(|| GraphError::UnknownPort(sink, port.to_string()))?;
+        self.check_sources(sink, position + 1)?;
+        let edge = self
+            .graph
+            .edges_directed(sink, Incoming)
+            .find(|edge| *edge.weight() == position)
+            .map(|edge| edge.id());
+        if let Some(edge) = edge {
+            self.graph.remove_edge(edge);
+        }
+        self.graph.add_edge(source, sink, position);
+        self.update_order()
     }
 
     /// Set multiple sources for the `sink` node.
     /// It clears `sink`'s sources before connecting.
     /// `source`s' outputs are layouted in `sink` input buffer in the provided order.
     /// Ref `Module::sample` doc for an example of input layout.
-    pub fn set_sources(&mut self, sink: NodeIndex, sources: &[NodeIndex]) {
+    pub fn set_sources(
+        &mut self,
+        sink: NodeIndex,
+        sources: &[NodeIndex],
+    ) -> Result<(), GraphError> {
+        self.check_sources(sink, sources.len())?;
         self.clear_sources(sink);
-        // ref `sample` method comments for the reason of reversing sources
-        for source in sources.iter().rev() {
-            self.graph.update_edge(*source, sink, ());
+        // add_edge instead of update_edge to allow the same source to be connected several times
+        for (position, source) in sources.iter().enumerate() {
+            self.graph.add_edge(*source, sink, position);
         }
-        self.update_order();
+        self.update_order()
     }
 
-    pub fn set_sources_rev(&mut self, sink: NodeIndex, sources: &[NodeIndex]) {
+    /// Like `set_sources`, but sources are layouted in the reversed order.
+    pub fn set_sources_rev(
+        &mut self,
+        sink: NodeIndex,
+        sources: &[NodeIndex],
+    ) -> Result<(), GraphError> {
+        self.check_sources(sink, sources.len())?;
         self.clear_sources(sink);
-        // ref `sample` method comments for the reason of reversing sources
-        for source in sources.iter() {
-            self.graph.update_edge(*source, sink, ());
+        for (position, source) in sources.iter().rev().enumerate() {
+            self.graph.add_edge(*source, sink, position);
         }
-        self.update_order();
+        self.update_order()
     }
 
     pub fn clear(&mut self) {
         self.order.clear();
+        self.paths.clear();
         self.graph.clear();
     }
 
     /// Update node traversal order.
     /// It must be called after any connection change.
-    pub fn update_order(&mut self) {
-        self.order = toposort(&self.graph, Some(&mut self.space)).unwrap_or_else(|_| vec![]);
+    ///
+    /// Graph with a cycle can't be sampled, so it produces silence until the cycle is broken.
+    /// Returned error lists nodes which form the cycle.
+    pub fn update_order(&mut self) -> Result<(), GraphError> {
+        self.update_paths();
+        match toposort(&self.graph, Some(&mut self.space)) {
+            Ok(order) => {
+                self.order = order;
+                self.update_buffers();
+                Ok(())
+            }
+            Err(cycle) => {
+                self.order.clear();
+                let node = cycle.node_id();
+                let nodes = tarjan_scc(&self.graph)
+                    .into_iter()
+                    .find(|component| component.contains(&node))
+                    .unwrap_or_else(|| vec![node]);
+                Err(GraphError::Cycle(nodes))
+            }
+        }
+    }
+
+    /// Move state of nodes of the `previous` graph into the nodes of this graph which are at the
+    /// same structural position. Ref `Module::transplant` and `update_paths`.
+    ///
+    /// It doesn't allocate and is intended to be called on the audio thread right before
+    /// switching to the new graph. Return whether every node of both graphs has a counterpart of
+    /// the same kind, i.e. only constants changed and both graphs play correlated signals.
+    pub fn transplant(&mut self, previous: &mut AudioGraph) -> bool {
+        // Modules rely on this check and don't compare their frame sizes.
+        if self.channels != previous.channels {
+            return false;
+        }
+        let mut same = self.paths.len() == previous.paths.len();
+        let (mut i, mut j) = (0, 0);
+        while i < self.paths.len() && j < previous.paths.len() {
+            let (path, idx) = &self.paths[i];
+            let (previous_path, previous_idx) = &previous.paths[j];
+            match path.cmp(previous_path) {
+                Ordering::Less => {
+                    same = false;
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    same = false;
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    // Deref boxes, otherwise `as_any` would return the Box itself.
+                    let node = &mut *self.graph[*idx];
+                    let previous_node = &mut *previous.graph[*previous_idx];
+                    same &= node.as_any().type_id() == previous_node.as_any().type_id();
+                    node.transplant(previous_node);
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        same
+    }
+
+    /// Compute structural paths of nodes.
+    ///
+    /// Path starts with the ordinal number of the sink (node without outgoing connections) and
+    /// continues with input ports leading from the sink to the node. E.g. in `440 s 0.5 *`
+    /// Constant(440) has path [0, 0, 0] and Constant(0.5) has [0, 1]. If node is reachable by
+    /// several paths, the first one in depth-first order is used.
+    ///
+    /// Paths don't depend on module kinds and sources of the node, so they survive edits which
+    /// change parameters of the patch without rearranging it.
+    fn update_paths(&mut self) {
+        self.paths.clear();
+        let mut visited = HashSet::new();
+        let sinks = self
+            .graph
+            .node_indices()
+            .filter(|idx| {
+                self.graph
+                    .neighbors_directed(*idx, Outgoing)
+                    .next()
+                    .is_none()
+            })
+            .collect::<Vec<_>>();
+        for (i, sink) in sinks.into_iter().enumerate() {
+            let mut stack = vec![(vec![i], sink)];
+            while let Some((path, idx)) = stack.pop() {
+                if !visited.insert(idx) {
+                    continue;
+                }
+                // Push sources in reverse to visit them in input order.
+                let mut sources = self
+                    .graph
+                    .edges_directed(idx, Incoming)
+                    .map(|edge| (*edge.weight(), edge.source()))
+                    .collect::<Vec<_>>();
+                sources.sort();
+                for (position, source) in sources.into_iter().rev() {
+                    let mut source_path = path.clone();
+                    source_path.push(position);
+                    stack.push((source_path, source));
+                }
+                self.paths.push((path, idx));
+            }
+        }
+        self.paths.sort();
+    }
+
+    /// Allocate input and output buffers for the current set of nodes.
+    fn update_buffers(&mut self) {
+        let inputs = self
+            .order
+            .iter()
+            .map(|idx| self.graph[*idx].inputs())
+            .max()
+            .unwrap_or(0);
+        let input_len = self.channels * inputs;
+        self.input
+            .resize(input_len.max(self.channels + self.parameters), 0.0);
+        self.block_input.resize(BLOCK * input_len, 0.0);
+        self.block = if self.order.iter().any(|idx| self.graph[*idx].per_frame()) {
+            1
+        } else {
+            BLOCK
+        };
+        let len = BLOCK * self.channels;
+        self.blocks
+            .resize_with(self.graph.node_bound(), || vec![0.0; len]);
+    }
+
+    /// Check that `sink` has room for `sources`. Input buffers are sized by `Module::inputs`, so
+    /// extra sources would be written past the sink's part of them.
+    fn check_sources(&self, sink: NodeIndex, sources: usize) -> Result<(), GraphError> {
+        let inputs = self.graph[sink].inputs();
+        if sources > inputs {
+            Err(GraphError::TooManySources(sink, inputs))
+        } else {
+            Ok(())
+        }
     }
 
     /// Remove all incoming connections of the node.
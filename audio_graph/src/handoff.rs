@@ -0,0 +1,127 @@
+//! # Handoff
+//!
+//! Real-time safe exchange of boxed values (e.g. freshly compiled AudioGraph) between a thread
+//! which builds them and an audio thread which uses them.
+//!
+//! There is a single slot in each direction. `Sender` puts a new value into the pending slot,
+//! `Receiver` takes it from there and puts the value it replaced into the retired slot, from
+//! where `Sender` picks it up for destruction. Receiving side never blocks, never allocates and
+//! never deallocates: all it does is swapping pointers.
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Arc;
+
+struct Slots<T> {
+    /// Value sent but not received yet.
+    pending: AtomicPtr<T>,
+    /// Value released by receiver and waiting for destruction.
+    retired: AtomicPtr<T>,
+    marker: PhantomData<Box<T>>,
+}
+
+// Values are moved between threads but never shared, so T: Sync is not required.
+unsafe impl<T: Send> Send for Slots<T> {}
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+impl<T> Slots<T> {
+    fn take(slot: &AtomicPtr<T>) -> Option<Box<T>> {
+        let p = slot.swap(ptr::null_mut(), Ordering::AcqRel);
+        if p.is_null() {
+            None
+        } else {
+            // Pointers in slots always come from Box::into_raw and are taken out only once.
+            Some(unsafe { Box::from_raw(p) })
+        }
+    }
+}
+
+impl<T> Drop for Slots<T> {
+    fn drop(&mut self) {
+        Slots::take(&self.pending);
+        Slots::take(&self.retired);
+    }
+}
+
+/// Create connected sender and receiver.
+pub fn channel<T: Send>() -> (Sender<T>, Receiver<T>) {
+    let slots = Arc::new(Slots {
+        pending: AtomicPtr::new(ptr::null_mut()),
+        retired: AtomicPtr::new(ptr::null_mut()),
+        marker: PhantomData,
+    });
+    (
+        Sender {
+            slots: slots.clone(),
+        },
+        Receiver { slots },
+    )
+}
+
+/// Non-real-time end of the handoff. Could be cloned to send from several threads.
+pub struct Sender<T> {
+    slots: Arc<Slots<T>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender {
+            slots: self.slots.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Send the value. If receiver has not picked up the previously sent one yet, it is replaced
+    /// and destroyed.
+    pub fn send(&self, value: Box<T>) {
+        self.collect();
+        let p = self
+            .slots
+            .pending
+            .swap(Box::into_raw(value), Ordering::AcqRel);
+        if !p.is_null() {
+            drop(unsafe { Box::from_raw(p) });
+        }
+    }
+
+    /// Destroy the value retired by receiver, if any.
+    /// Should be called periodically, otherwise receiver is not able to pick up new values.
+    pub fn collect(&self) {
+        Slots::take(&self.slots.retired);
+    }
+}
+
+/// Real-time end of the handoff.
+pub struct Receiver<T> {
+    slots: Arc<Slots<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Take the value sent since the last call, if any.
+    ///
+    /// It returns `None` while the previously retired value is not collected by sender, so it is
+    /// always possible to `retire` the value replaced by the received one.
+    pub fn receive(&self) -> Option<Box<T>> {
+        if self.slots.retired.load(Ordering::Acquire).is_null() {
+            Slots::take(&self.slots.pending)
+        } else {
+            None
+        }
+    }
+
+    /// Pass the value to sender for destruction.
+    /// The value is given back if the retired slot is still occupied.
+    pub fn retire(&self, value: Box<T>) -> Result<(), Box<T>> {
+        let p = Box::into_raw(value);
+        match self.slots.retired.compare_exchange(
+            ptr::null_mut(),
+            p,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(unsafe { Box::from_raw(p) }),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! # Stack language
+//!
+//! Compiler of Sound Garden's stack language into AudioGraph.
+//!
+//! Program is a whitespace-separated sequence of tokens which are evaluated from left to right.
+//! Each token is either a number (which becomes a Constant module), a stack operation or a module
+//! from the `ModuleRegistry`.
+//! Module pops as many nodes from the stack as it has inputs, connects them as its sources and
+//! pushes itself onto the stack. E.g. `440 s 0.5 *` is a sine wave of 440 Hz at half amplitude.
+//! If the stack runs out, the rest of inputs take default values of their ports, e.g. in
+//! `440 s 1000 bqlpf` the filter has Q of 0.707. Inputs without defaults are required.
+//!
+//! Stack operations:
+//! * `pop` drops the top of the stack;
+//! * `swap` swaps two topmost nodes;
+//! * `dup` duplicates the top of the stack;
+//! * `rot` rotates three topmost nodes, moving the third one to the top.
+//!
+//! Feedback loops are closed with named unit delays: `send:<name>` passes the top of the stack
+//! through and `recv:<name>` pushes what was sent on the previous frame. Receive could go before
+//! the send, e.g. `recv:x 0.5 * in + send:x` mixes input with its own echo one sample later.
+use crate::graph::{AudioGraph, GraphError};
+use crate::modules::constant::Constant;
+use crate::modules::unit_delay::{UnitDelayCell, UnitDelayReceive, UnitDelaySend};
+use crate::registry::ModuleRegistry;
+use crate::sample::Sample;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// Stack operations with their descriptions, for help output.
+pub const STACK_OPERATIONS: &[(&str, &str)] = &[
+    ("pop", "drop the top of the stack"),
+    ("swap", "swap two topmost nodes"),
+    ("dup", "duplicate the top of the stack"),
+    ("rot", "move the third node from the top to the top"),
+    (
+        "send:<name>",
+        "pass the top of the stack to `recv:<name>` on the next frame",
+    ),
+    ("recv:<name>", "push the previous frame of `send:<name>`"),
+];
+
+/// What went wrong during compilation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Module requires more sources than there are nodes on the stack.
+    NotEnoughInputs,
+    /// Like `NotEnoughInputs`, naming the first input port without a source and a default.
+    MissingInput(&'static str),
+    NothingToPop,
+    NothingToSwap,
+    NothingToDup,
+    NothingToRot,
+    /// Token is neither a module, nor a number, nor a stack operation.
+    UnknownModule,
+    /// Module constructor rejected token arguments.
+    InvalidArguments(String),
+    /// Connecting the node made a cycle.
+    Cycle(GraphError),
+    /// `recv:<name>` without `send:<name>`.
+    NoSend,
+    /// The second `send:<name>` with the same name.
+    DuplicateSend,
+}
+
+/// Compilation error pointing to the offending token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompileError {
+    /// Zero-based index of the token in the program.
+    pub index: usize,
+    /// Byte range of the token in the program text.
+    pub span: Range<usize>,
+    /// The token itself.
+    pub token: String,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Users count tokens from one.
+        let n = self.index + 1;
+        match &self.kind {
+            ErrorKind::NotEnoughInputs => write!(
+                f,
+                "Node #{} `{}` has not enough inputs on the stack.",
+                n, self.token
+            ),
+            ErrorKind::MissingInput(port) => {
+                write!(f, "Node #{} `{}`: missing input `{}`.", n, self.token, port)
+            }
+            ErrorKind::NothingToPop => write!(f, "Nothing to pop at #{}!", n),
+            ErrorKind::NothingToSwap => write!(f, "Nothing to swap at #{}!", n),
+            ErrorKind::NothingToDup => write!(f, "Nothing to dup at #{}!", n),
+            ErrorKind::NothingToRot => write!(f, "Nothing to rot at #{}!", n),
+            ErrorKind::UnknownModule => {
+                write!(f, "Node #{} `{}` is unknown module.", n, self.token)
+            }
+            ErrorKind::InvalidArguments(msg) => write!(f, "Node #{} `{}`: {}.", n, self.token, msg),
+            ErrorKind::Cycle(e) => write!(f, "Node #{} `{}` closes a cycle. {}", n, self.token, e),
+            ErrorKind::NoSend => write!(f, "Node #{} `{}` has no matching send.", n, self.token),
+            ErrorKind::DuplicateSend => {
+                write!(f, "Node #{} `{}` is sent more than once.", n, self.token)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Split program text into whitespace-separated tokens along with their byte spans.
+pub fn tokenize(text: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                tokens.push((s..i, &text[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s..text.len(), &text[s..]));
+    }
+    tokens
+}
+
+/// Compile program `text` into AudioGraph using built-in modules.
+///
+/// `parameters` is the number of external parameters available to `param:<N>` modules, it
+/// defines the layout of the input frame expected by `AudioGraph::sample`.
+pub fn compile(
+    text: &str,
+    channels: usize,
+    sample_rate: usize,
+    parameters: usize,
+) -> Result<AudioGraph, CompileError> {
+    compile_with(
+        &ModuleRegistry::default(),
+        text,
+        channels,
+        sample_rate,
+        parameters,
+    )
+}
+
+/// Compile program `text` into AudioGraph looking modules up in the `registry`.
+pub fn compile_with(
+    registry: &ModuleRegistry,
+    text: &str,
+    channels: usize,
+    sample_rate: usize,
+    parameters: usize,
+) -> Result<AudioGraph, CompileError> {
+    let tokens = tokenize(text);
+    let error = |index: usize, kind| {
+        let (span, token): &(Range<usize>, &str) = &tokens[index];
+        CompileError {
+            index,
+            span: span.clone(),
+            token: token.to_string(),
+            kind,
+        }
+    };
+    // Receive may precede its send, so collect sends beforehand.
+    let mut cells = HashMap::new();
+    for (index, (_, token)) in tokens.iter().enumerate() {
+        if let Some(name) = token.strip_prefix("send:") {
+            if cells.insert(name, UnitDelayCell::new(channels)).is_some() {
+                return Err(error(index, ErrorKind::DuplicateSend));
+            }
+        }
+    }
+    let mut g = AudioGraph::new(channels, parameters);
+    let mut stack = Vec::new();
+    for (index, (_, token)) in tokens.iter().enumerate() {
+        let token = *token;
+        let error = |kind| error(index, kind);
+        let node = if let Some(name) = token.strip_prefix("send:") {
+            let cell = cells[name].clone();
+            Some(Ok(Box::new(UnitDelaySend::new(cell)) as _))
+        } else if let Some(name) = token.strip_prefix("recv:") {
+            match cells.get(name) {
+                Some(cell) => Some(Ok(Box::new(UnitDelayReceive::new(cell.clone())) as _)),
+                None => return Err(error(ErrorKind::NoSend)),
+            }
+        } else {
+            match token.parse::<Sample>() {
+                Ok(x) => Some(Ok(Box::new(Constant::new(channels, x)) as _)),
+                Err(_) => registry.construct(token, channels, sample_rate, parameters),
+            }
+        };
+        match node {
+            Some(Err(msg)) => return Err(error(ErrorKind::InvalidArguments(msg))),
+            Some(Ok(node)) => {
+                let idx = g.add_node(node);
+                let inputs = g.node(idx).inputs();
+                let mut sources = stack.split_off(stack.len() - stack.len().min(inputs));
+                if sources.len() < inputs {
+                    // Partial application: the rest of inputs take their default values.
+                    let defaults = match g.node(idx).input_ports().get(sources.len()..inputs) {
+                        Some(ports) => ports
+                            .iter()
+                            .map(|port| port.default.ok_or(ErrorKind::MissingInput(port.name)))
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(error)?,
+                        None => return Err(error(ErrorKind::NotEnoughInputs)),
+                    };
+                    for x in defaults {
+                        sources.push(g.add_node(Box::new(Constant::new(channels, x))));
+                    }
+                }
+                g.set_sources(idx, &sources)
+                    .map_err(|e| error(ErrorKind::Cycle(e)))?;
+                stack.push(idx);
+            }
+            None => match token {
+                "pop" => {
+                    if stack.pop().is_none() {
+                        return Err(error(ErrorKind::NothingToPop));
+                    }
+                }
+                "swap" => {
+                    let len = stack.len();
+                    if len < 2 {
+                        return Err(error(ErrorKind::NothingToSwap));
+                    }
+                    stack.swap(len - 2, len - 1);
+                }
+                "dup" => match stack.last() {
+                    Some(idx) => {
+                        let idx = *idx;
+                        stack.push(idx);
+                    }
+                    None => return Err(error(ErrorKind::NothingToDup)),
+                },
+                "rot" => {
+                    let len = stack.len();
+                    if len < 3 {
+                        return Err(error(ErrorKind::NothingToRot));
+                    }
+                    stack.swap(len - 2, len - 1);
+                    stack.swap(len - 3, len - 1);
+                }
+                _ => return Err(error(ErrorKind::UnknownModule)),
+            },
+        }
+    }
+    Ok(g)
+}
+
+/// Human-readable description of the language: modules from the `registry` and stack operations.
+pub fn help(registry: &ModuleRegistry) -> String {
+    let mut help = registry.help();
+    for (name, doc) in STACK_OPERATIONS {
+        help.push_str(&format!("{}: {}\n", name, doc));
+    }
+    help
+}
@@ -4,11 +4,14 @@
 //! audio signal modules. BYO audio driver or audio file encoder to play or record generated sound.
 extern crate fixedbitset;
 extern crate petgraph;
-extern crate rand;
 
 pub mod graph;
+pub mod handoff;
+pub mod lang;
 pub mod module;
 pub mod modules;
 pub mod prelude;
 pub mod pure;
+pub mod random;
+pub mod registry;
 pub mod sample;
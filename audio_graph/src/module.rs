@@ -1,10 +1,83 @@
 //! # Module
 use crate::sample::{Frame, Sample};
+use std::any::Any;
+
+/// Upcast to `Any`, which allows Modules to downcast their counterparts in `transplant`.
+/// It is implemented automatically for all `'static` types.
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Description of Module's input or output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Port {
+    /// Short name to refer the port by, e.g. in `AudioGraph::connect_port`.
+    pub name: &'static str,
+    /// Unit of measurement like "Hz" or "s", empty if there is none.
+    pub unit: &'static str,
+    /// Range of values the module expects. It is informative and is not enforced.
+    pub range: (Sample, Sample),
+    /// Value to use when nothing is connected to the port.
+    pub default: Option<Sample>,
+}
+
+impl Port {
+    pub const fn new(name: &'static str) -> Self {
+        Port {
+            name,
+            unit: "",
+            range: (Sample::NEG_INFINITY, Sample::INFINITY),
+            default: None,
+        }
+    }
+
+    pub const fn unit(self, unit: &'static str) -> Self {
+        Port { unit, ..self }
+    }
+
+    pub const fn range(self, min: Sample, max: Sample) -> Self {
+        Port {
+            range: (min, max),
+            ..self
+        }
+    }
+
+    pub const fn default(self, x: Sample) -> Self {
+        Port {
+            default: Some(x),
+            ..self
+        }
+    }
+}
 
 /// Defines behavior of sound-producing node.
-pub trait Module {
+pub trait Module: AsAny {
+    /// Descriptions of inputs, in the order of sources. Ref `sample` for the layout.
+    fn input_ports(&self) -> &[Port] {
+        &[]
+    }
+
+    /// Description of the output. Module has a single output with a sample per channel.
+    fn output_port(&self) -> Port {
+        Port::new("out")
+    }
+
     /// How many sources must be connected to this module.
-    fn inputs(&self) -> u8;
+    /// Modules with named inputs don't need to override it.
+    fn inputs(&self) -> usize {
+        self.input_ports().len()
+    }
 
     /// Get Module's current frame.
     ///
@@ -17,7 +90,7 @@ pub trait Module {
     ///
     /// This is example of how multi-channel multi-source outputs are collected into the
     /// input buffer (stereo audio and 3 incoming connections case):
-    /// ```
+    /// ```text
     /// first source output: [ 0 1 ]
     ///                        | |
     ///                        | +--------+
@@ -32,4 +105,38 @@ pub trait Module {
     /// sink input:                   [ 0 1 2 3 4 5 ]
     /// ```
     fn sample(&mut self, input: &[Sample]);
+
+    /// Compute `frames` frames at once.
+    ///
+    /// `input` contains `frames` consecutive input frames laid out as for `sample`, and `output`
+    /// receives `frames` consecutive output frames. Implementations must leave `output()` equal
+    /// to the last computed frame.
+    ///
+    /// Default implementation calls `sample` for each frame. Override it if module can process
+    /// block faster, e.g. by avoiding per-frame overhead or by vectorising.
+    fn process_block(&mut self, input: &[Sample], output: &mut [Sample], frames: usize) {
+        let stride = input.len() / frames;
+        let channels = output.len() / frames;
+        for (x, y) in input.chunks(stride).zip(output.chunks_mut(channels)) {
+            self.sample(x);
+            y.clone_from_slice(self.output());
+        }
+    }
+
+    /// Whether the whole graph has to be processed frame by frame when this module is in it.
+    /// It is the case for modules which talk to each other outside of graph connections, like
+    /// unit delays.
+    fn per_frame(&self) -> bool {
+        false
+    }
+
+    /// Take over the state (phase, buffers etc.) of the `previous` incarnation of this module
+    /// when the graph is recompiled, so edits don't reset the whole patch.
+    ///
+    /// `previous` could be of any type, implementations should downcast it and ignore unmatched.
+    /// It is called on the audio thread, so it must not allocate and must be cheap. Small state is
+    /// copied because the previous graph keeps playing during crossfade. Large buffers must be
+    /// shared instead, with the previous incarnation only reading them while it fades out, and
+    /// whatever the new one allocated has to be handed to the previous one to be freed with it.
+    fn transplant(&mut self, _previous: &mut dyn Module) {}
 }
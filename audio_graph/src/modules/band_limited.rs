@@ -0,0 +1,135 @@
+//! # Band-limited oscillators
+//!
+//! Saw, square and triangle waves in the range -1..1 with reduced aliasing.
+//!
+//! Like Phasor, they accumulate phase from frequency input, so frequency could be variable. The
+//! naive waveform is corrected around its discontinuities with polynomial residuals: PolyBLEP for
+//! jumps of saw and square, PolyBLAMP for corners of triangle. Naive oscillators are cheaper and
+//! still fine for LFOs.
+//!
+//! Sources to connect: frequency, and width for square.
+use crate::module::{Module, Port};
+use crate::sample::{Frame, Sample};
+
+pub const INPUTS: &[Port] = &[Port::new("freq").unit("Hz").range(0.0, 20_000.0)];
+
+pub const SQUARE_INPUTS: &[Port] = &[
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("width").range(0.0, 1.0).default(0.5),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Saw,
+    Square,
+    Triangle,
+}
+
+pub struct BandLimited {
+    waveform: Waveform,
+    /// Phase per channel in 0..1.
+    phases: Vec<Sample>,
+    sample_rate: Sample,
+    output: Vec<Sample>,
+}
+
+impl BandLimited {
+    pub fn new(channels: usize, sample_rate: usize, waveform: Waveform) -> Self {
+        BandLimited {
+            waveform,
+            phases: vec![0.0; channels],
+            sample_rate: sample_rate as Sample,
+            output: vec![0.0; channels],
+        }
+    }
+}
+
+impl Module for BandLimited {
+    fn input_ports(&self) -> &[Port] {
+        match self.waveform {
+            Waveform::Square => SQUARE_INPUTS,
+            Waveform::Saw | Waveform::Triangle => INPUTS,
+        }
+    }
+
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, input: &Frame) {
+        let channels = self.output.len();
+        for (channel, (output, phase)) in self
+            .output
+            .iter_mut()
+            .zip(self.phases.iter_mut())
+            .enumerate()
+        {
+            // Phase increment per frame.
+            let dt = input[channel] / self.sample_rate;
+            *phase = wrap(*phase + dt);
+            let t = *phase;
+            // With negative frequency both the jump and the time since it flip, and residuals
+            // are odd (BLEP) or even (BLAMP) in time, so corrections in terms of phase are the
+            // same and only need the size of the increment.
+            let dt = dt.abs();
+            *output = match self.waveform {
+                Waveform::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+                Waveform::Square => {
+                    // Keep edges at least a frame apart, otherwise their corrections overlap
+                    // and produce a spike. Not `clamp`, which panics when `dt > 0.5`.
+                    let width = input[channels + channel].max(dt).min(1.0 - dt);
+                    let naive = if t <= width { 1.0 } else { -1.0 };
+                    naive + poly_blep(t, dt) - poly_blep(wrap(t - width), dt)
+                }
+                Waveform::Triangle => {
+                    let naive = if t < 0.5 {
+                        4.0 * t - 1.0
+                    } else {
+                        3.0 - 4.0 * t
+                    };
+                    naive + 4.0 * dt * (poly_blamp(t, dt) - poly_blamp(wrap(t + 0.5), dt))
+                }
+            };
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.phases.clone_from_slice(&previous.phases);
+        }
+    }
+}
+
+fn wrap(t: Sample) -> Sample {
+    t - t.floor()
+}
+
+/// Residual of band-limited step from -1 to 1 at phase 0, `t` and `dt` as in `BandLimited`.
+fn poly_blep(t: Sample, dt: Sample) -> Sample {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let x = t / dt;
+        2.0 * x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Residual of band-limited ramp (integrated step) at phase 0.
+fn poly_blamp(t: Sample, dt: Sample) -> Sample {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let x = t / dt - 1.0;
+        -x * x * x / 3.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 3.0
+    } else {
+        0.0
+    }
+}
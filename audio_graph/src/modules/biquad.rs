@@ -1,16 +1,21 @@
 //! BiQuad Filters
 //!
-//! Sources to connect: input, cut-off frequency, Q.
-use crate::module::Module;
+//! Coefficients follow Robert Bristow-Johnson's Audio EQ Cookbook.
+//!
+//! Sources to connect: input, cut-off frequency, Q, and gain in dB for peaking EQ and shelves.
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 
+/// Compute coefficients `(b0, b1, b2, a0, a1, a2)` from sine and cosine of angular frequency,
+/// alpha which depends on Q, and amplitude `a` which depends on gain (1 for filters without it).
 type MakeCoefficients =
-    fn(Sample, Sample, Sample) -> (Sample, Sample, Sample, Sample, Sample, Sample);
+    fn(Sample, Sample, Sample, Sample) -> (Sample, Sample, Sample, Sample, Sample, Sample);
 
 pub fn make_lpf_coefficients(
     _sin_o: Sample,
     cos_o: Sample,
     alpha: Sample,
+    _a: Sample,
 ) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
     let b1 = 1.0 - cos_o;
     let b0 = 0.5 * b1;
@@ -21,6 +26,7 @@ pub fn make_hpf_coefficients(
     _sin_o: Sample,
     cos_o: Sample,
     alpha: Sample,
+    _a: Sample,
 ) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
     let k = 1.0 + cos_o;
     let b0 = 0.5 * k;
@@ -28,8 +34,115 @@ pub fn make_hpf_coefficients(
     (b0, b1, b0, 1.0 + alpha, -2.0 * cos_o, 1.0 - alpha)
 }
 
+/// Band-pass with constant skirt gain, peak gain is Q.
+pub fn make_bpf_skirt_coefficients(
+    sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    _a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    let b0 = 0.5 * sin_o;
+    (b0, 0.0, -b0, 1.0 + alpha, -2.0 * cos_o, 1.0 - alpha)
+}
+
+/// Band-pass with constant 0 dB peak gain.
+pub fn make_bpf_peak_coefficients(
+    _sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    _a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos_o, 1.0 - alpha)
+}
+
+pub fn make_notch_coefficients(
+    _sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    _a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    let b1 = -2.0 * cos_o;
+    (1.0, b1, 1.0, 1.0 + alpha, b1, 1.0 - alpha)
+}
+
+pub fn make_allpass_coefficients(
+    _sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    _a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    let b1 = -2.0 * cos_o;
+    (1.0 - alpha, b1, 1.0 + alpha, 1.0 + alpha, b1, 1.0 - alpha)
+}
+
+pub fn make_peak_coefficients(
+    _sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    let b1 = -2.0 * cos_o;
+    (
+        1.0 + alpha * a,
+        b1,
+        1.0 - alpha * a,
+        1.0 + alpha / a,
+        b1,
+        1.0 - alpha / a,
+    )
+}
+
+pub fn make_low_shelf_coefficients(
+    _sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    let k = 2.0 * a.sqrt() * alpha;
+    (
+        a * ((a + 1.0) - (a - 1.0) * cos_o + k),
+        2.0 * a * ((a - 1.0) - (a + 1.0) * cos_o),
+        a * ((a + 1.0) - (a - 1.0) * cos_o - k),
+        (a + 1.0) + (a - 1.0) * cos_o + k,
+        -2.0 * ((a - 1.0) + (a + 1.0) * cos_o),
+        (a + 1.0) + (a - 1.0) * cos_o - k,
+    )
+}
+
+pub fn make_high_shelf_coefficients(
+    _sin_o: Sample,
+    cos_o: Sample,
+    alpha: Sample,
+    a: Sample,
+) -> (Sample, Sample, Sample, Sample, Sample, Sample) {
+    let k = 2.0 * a.sqrt() * alpha;
+    (
+        a * ((a + 1.0) + (a - 1.0) * cos_o + k),
+        -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_o),
+        a * ((a + 1.0) + (a - 1.0) * cos_o - k),
+        (a + 1.0) - (a - 1.0) * cos_o + k,
+        2.0 * ((a - 1.0) - (a + 1.0) * cos_o),
+        (a + 1.0) - (a - 1.0) * cos_o - k,
+    )
+}
+
+pub const INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("q").range(0.0, 10.0).default(0.707),
+];
+
+pub const GAIN_INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("q").range(0.0, 10.0).default(0.707),
+    Port::new("gain").unit("dB").range(-48.0, 48.0).default(0.0),
+];
+
 pub struct BiQuad {
     make_coefficients: MakeCoefficients,
+    /// Whether filter has gain input.
+    gain: bool,
     output: Vec<Sample>,
     sample_angular_period: Sample,
     x1: Vec<Sample>,
@@ -42,6 +155,7 @@ impl BiQuad {
         let sample_angular_period = 2.0 * std::f64::consts::PI / sample_rate as f64;
         BiQuad {
             make_coefficients,
+            gain: false,
             output: vec![0.0; channels],
             sample_angular_period,
             x1: vec![0.0; channels],
@@ -49,11 +163,27 @@ impl BiQuad {
             y2: vec![0.0; channels],
         }
     }
+
+    /// Filter with gain input, for peaking EQ and shelves.
+    pub fn with_gain(
+        channels: usize,
+        sample_rate: usize,
+        make_coefficients: MakeCoefficients,
+    ) -> Self {
+        BiQuad {
+            gain: true,
+            ..BiQuad::new(channels, sample_rate, make_coefficients)
+        }
+    }
 }
 
 impl Module for BiQuad {
-    fn inputs(&self) -> u8 {
-        3
+    fn input_ports(&self) -> &[Port] {
+        if self.gain {
+            GAIN_INPUTS
+        } else {
+            INPUTS
+        }
     }
 
     fn output(&self) -> &Frame {
@@ -66,6 +196,11 @@ impl Module for BiQuad {
             let x = input[channel];
             let freq = input[channel + channels];
             let q = input[channel + 2 * channels];
+            let a = if self.gain {
+                Sample::powf(10.0, input[channel + 3 * channels] / 40.0)
+            } else {
+                1.0
+            };
 
             let x1 = self.x1[channel];
             let x2 = self.x2[channel];
@@ -76,7 +211,7 @@ impl Module for BiQuad {
             let sin_o = o.sin();
             let cos_o = o.cos();
             let alpha = sin_o / (2.0 * q);
-            let (b0, b1, b2, a0, a1, a2) = (self.make_coefficients)(sin_o, cos_o, alpha);
+            let (b0, b1, b2, a0, a1, a2) = (self.make_coefficients)(sin_o, cos_o, alpha, a);
             *y = (x * b0 + x1 * b1 + x2 * b2 - y1 * a1 - y2 * a2) / a0;
 
             self.x2[channel] = x1;
@@ -84,4 +219,13 @@ impl Module for BiQuad {
             self.y2[channel] = y1;
         }
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.x1.clone_from_slice(&previous.x1);
+            self.x2.clone_from_slice(&previous.x2);
+            self.y2.clone_from_slice(&previous.y2);
+        }
+    }
 }
@@ -19,13 +19,15 @@ impl Constant {
 }
 
 impl Module for Constant {
-    fn inputs(&self) -> u8 {
-        0
-    }
-
     fn output(&self) -> &Frame {
         &self.values
     }
 
     fn sample(&mut self, _input: &Frame) {}
+
+    fn process_block(&mut self, _input: &[Sample], output: &mut [Sample], _frames: usize) {
+        for y in output.chunks_mut(self.values.len()) {
+            y.clone_from_slice(&self.values);
+        }
+    }
 }
@@ -3,11 +3,25 @@
 //! Variable signal delay up to maximum period.
 //!
 //! Sources to connect: input to delay, delay time.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub const INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("time").unit("s").range(0.0, 60.0),
+];
 
 pub struct Delay {
-    buffer: Vec<Sample>,
+    /// Frames of samples as bits. The line is shared with the next incarnation of the node, so
+    /// the old graph keeps playing the same tail while it fades out. Ref `transplant`.
+    buffer: Arc<Vec<AtomicU64>>,
+    /// Only the latest incarnation writes into the shared line.
+    writes: bool,
+    /// Line allocated for the next incarnation which took this one's instead. It is kept here
+    /// to be freed along with the old graph rather than on the audio thread.
+    spare: Option<Arc<Vec<AtomicU64>>>,
     channels: usize,
     mask: usize,
     frame_number: usize,
@@ -23,9 +37,13 @@ impl Delay {
         let max_delay_frames =
             ((sample_rate as Sample * max_delay) as usize + 1).next_power_of_two();
         let mask = max_delay_frames - 1;
-        let buffer = vec![0.0; channels * max_delay_frames];
+        let buffer = (0..channels * max_delay_frames)
+            .map(|_| AtomicU64::new(0))
+            .collect();
         Delay {
-            buffer,
+            buffer: Arc::new(buffer),
+            writes: true,
+            spare: None,
             channels,
             frame_number: 0,
             mask,
@@ -35,9 +53,15 @@ impl Delay {
     }
 }
 
+impl Delay {
+    fn read(&self, i: usize) -> Sample {
+        Sample::from_bits(self.buffer[i].load(Ordering::Relaxed))
+    }
+}
+
 impl Module for Delay {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -47,17 +71,36 @@ impl Module for Delay {
     fn sample(&mut self, input: &Frame) {
         for channel in 0..self.channels {
             let x = input[channel];
-            let z = input[channel + self.channels] * self.sample_rate;
+            // Longer delays would wrap around the buffer.
+            let z =
+                (input[channel + self.channels] * self.sample_rate).min((self.mask - 1) as Sample);
             let delay = z as usize;
             let k = z.fract();
             if self.frame_number > delay {
                 let i = self.frame_number - delay;
-                let a = self.buffer[((i - 1) & self.mask) * self.channels + channel];
-                let b = self.buffer[(i & self.mask) * self.channels + channel];
+                let a = self.read(((i - 1) & self.mask) * self.channels + channel);
+                let b = self.read((i & self.mask) * self.channels + channel);
                 self.output[channel] = k * a + (1.0 - k) * b;
             }
-            self.buffer[(self.frame_number & self.mask) * self.channels + channel] = x;
+            if self.writes {
+                self.buffer[(self.frame_number & self.mask) * self.channels + channel]
+                    .store(x.to_bits(), Ordering::Relaxed);
+            }
         }
         self.frame_number += 1;
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any_mut().downcast_mut::<Self>() {
+            // Buffer could be tens of megabytes, copying it would cause a dropout. It is shared
+            // instead: the previous incarnation keeps reading it, but stops writing.
+            if previous.buffer.len() == self.buffer.len() {
+                let buffer = std::mem::replace(&mut self.buffer, previous.buffer.clone());
+                previous.spare = Some(buffer);
+                previous.writes = false;
+                self.output.clone_from_slice(&previous.output);
+                self.frame_number = previous.frame_number;
+            }
+        }
+    }
 }
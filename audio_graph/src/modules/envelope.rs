@@ -0,0 +1,229 @@
+//! # Envelope
+//!
+//! Envelope generators driven by gate signals, output is 0..1 to multiply oscillators with.
+//!
+//! * ADSR attacks to 1 when gate opens, decays to sustain level and holds it while gate is open,
+//!   then releases to 0.
+//! * AR is ADSR without decay, it holds 1 while gate is open.
+//! * Perc attacks to 1 and releases right away, gate only triggers it.
+//!
+//! Gate is open while it is above 0, envelope starts on its rising edge.
+//!
+//! Linear stages take exactly the given time. Exponential ones are one-pole curves which aim a
+//! bit past the target, like analog envelopes do, and take roughly the given time.
+//!
+//! On soft retrigger new attack starts from the current level, on hard one — from 0.
+//!
+//! Sources to connect: gate, attack, decay, sustain, release for ADSR; gate, attack, release for
+//! AR and perc.
+use crate::module::{Module, Port};
+use crate::sample::{Frame, Sample};
+
+pub const ADSR_INPUTS: &[Port] = &[
+    Port::new("gate"),
+    Port::new("attack").unit("s").range(0.0, 60.0).default(0.01),
+    Port::new("decay").unit("s").range(0.0, 60.0).default(0.1),
+    Port::new("sustain").range(0.0, 1.0).default(0.7),
+    Port::new("release").unit("s").range(0.0, 60.0).default(0.3),
+];
+
+pub const AR_INPUTS: &[Port] = &[
+    Port::new("gate"),
+    Port::new("attack").unit("s").range(0.0, 60.0).default(0.01),
+    Port::new("release").unit("s").range(0.0, 60.0).default(0.3),
+];
+
+pub const PERC_INPUTS: &[Port] = &[
+    Port::new("trigger"),
+    Port::new("attack")
+        .unit("s")
+        .range(0.0, 60.0)
+        .default(0.001),
+    Port::new("release").unit("s").range(0.0, 60.0).default(0.3),
+];
+
+/// How far above the target exponential attack aims, relative to full scale. The bigger it is,
+/// the closer the curve is to linear.
+const ATTACK_OVERSHOOT: Sample = 0.3;
+/// How far below the target exponential decay and release aim.
+const RELEASE_OVERSHOOT: Sample = 0.0001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Adsr,
+    Ar,
+    Perc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    Linear,
+    Exponential,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    stage: Stage,
+    level: Sample,
+    /// Level at the start of the current stage.
+    start: Sample,
+    gate: bool,
+}
+
+impl State {
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.start = self.level;
+    }
+}
+
+pub struct Envelope {
+    shape: Shape,
+    curve: Curve,
+    hard_retrigger: bool,
+    sample_rate: Sample,
+    states: Vec<State>,
+    output: Vec<Sample>,
+}
+
+impl Envelope {
+    pub fn new(
+        channels: usize,
+        sample_rate: usize,
+        shape: Shape,
+        curve: Curve,
+        hard_retrigger: bool,
+    ) -> Self {
+        let state = State {
+            stage: Stage::Idle,
+            level: 0.0,
+            start: 0.0,
+            gate: false,
+        };
+        Envelope {
+            shape,
+            curve,
+            hard_retrigger,
+            sample_rate: sample_rate as Sample,
+            states: vec![state; channels],
+            output: vec![0.0; channels],
+        }
+    }
+
+    /// Level after a frame of moving from `start` to `target` in `time` seconds. Attack rises,
+    /// other stages fall, so exponential curves never cross the target in the wrong direction
+    /// and the output stays within 0..1.
+    fn step(
+        &self,
+        stage: Stage,
+        level: Sample,
+        start: Sample,
+        target: Sample,
+        time: Sample,
+    ) -> Sample {
+        let frames = time * self.sample_rate;
+        if frames <= 1.0 {
+            return target;
+        }
+        match self.curve {
+            Curve::Linear => level + (target - start) / frames,
+            Curve::Exponential => {
+                let (overshoot, aim) = if stage == Stage::Attack {
+                    (ATTACK_OVERSHOOT, target + ATTACK_OVERSHOOT)
+                } else {
+                    (RELEASE_OVERSHOOT, target - RELEASE_OVERSHOOT)
+                };
+                let coefficient = (-((1.0 + overshoot) / overshoot).ln() / frames).exp();
+                aim + (level - aim) * coefficient
+            }
+        }
+    }
+}
+
+impl Module for Envelope {
+    fn input_ports(&self) -> &[Port] {
+        match self.shape {
+            Shape::Adsr => ADSR_INPUTS,
+            Shape::Ar => AR_INPUTS,
+            Shape::Perc => PERC_INPUTS,
+        }
+    }
+
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, input: &Frame) {
+        let channels = self.output.len();
+        for channel in 0..channels {
+            let x = |port: usize| input[port * channels + channel];
+            let (attack, decay, sustain, release) = match self.shape {
+                Shape::Adsr => (x(1), x(2), x(3).clamp(0.0, 1.0), x(4)),
+                Shape::Ar | Shape::Perc => (x(1), 0.0, 1.0, x(2)),
+            };
+            let mut state = self.states[channel];
+
+            let gate = x(0) > 0.0;
+            if gate && !state.gate {
+                if self.hard_retrigger {
+                    state.level = 0.0;
+                }
+                state.enter(Stage::Attack);
+            } else if !gate && state.gate && self.shape != Shape::Perc && state.stage != Stage::Idle
+            {
+                state.enter(Stage::Release);
+            }
+            state.gate = gate;
+
+            match state.stage {
+                Stage::Idle => state.level = 0.0,
+                Stage::Attack => {
+                    state.level = self.step(Stage::Attack, state.level, state.start, 1.0, attack);
+                    if state.level >= 1.0 {
+                        state.level = 1.0;
+                        state.enter(match self.shape {
+                            Shape::Adsr => Stage::Decay,
+                            Shape::Ar => Stage::Sustain,
+                            Shape::Perc => Stage::Release,
+                        });
+                    }
+                }
+                Stage::Decay => {
+                    state.level = self.step(Stage::Decay, state.level, state.start, sustain, decay);
+                    if state.level <= sustain {
+                        state.level = sustain;
+                        state.enter(Stage::Sustain);
+                    }
+                }
+                // Follow sustain level if it is modulated.
+                Stage::Sustain => state.level = sustain,
+                Stage::Release => {
+                    state.level = self.step(Stage::Release, state.level, state.start, 0.0, release);
+                    if state.level <= 0.0 {
+                        state.level = 0.0;
+                        state.enter(Stage::Idle);
+                    }
+                }
+            }
+
+            self.output[channel] = state.level;
+            self.states[channel] = state;
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.states.clone_from_slice(&previous.states);
+        }
+    }
+}
@@ -3,10 +3,16 @@
 //! Feedback comb filter with variable delay time and gain.
 //!
 //! Sources to connect: input to delay, delay time, gain.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::modules::delay::Delay;
 use crate::sample::{Frame, Sample};
 
+pub const INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("time").unit("s").range(0.0, 60.0),
+    Port::new("gain").range(0.0, 1.0).default(0.5),
+];
+
 pub struct Feedback {
     channels: usize,
     delay: Delay,
@@ -27,8 +33,8 @@ impl Feedback {
 }
 
 impl Module for Feedback {
-    fn inputs(&self) -> u8 {
-        3
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -51,4 +57,11 @@ impl Module for Feedback {
             *output = x + gain * delayed[channel];
         }
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any_mut().downcast_mut::<Self>() {
+            self.delay.transplant(&mut previous.delay);
+            self.output.clone_from_slice(&previous.output);
+        }
+    }
 }
@@ -3,9 +3,14 @@
 //! Basic IIR low/high-pass filters.
 //!
 //! Sources to connect: input, cut-off frequency.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 
+pub const INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+];
+
 pub struct LPF {
     output: Vec<Sample>,
     sample_angular_period: Sample,
@@ -22,8 +27,8 @@ impl LPF {
 }
 
 impl Module for LPF {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -40,6 +45,12 @@ impl Module for LPF {
             *value += a * (x - *value);
         }
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+        }
+    }
 }
 
 pub struct HPF {
@@ -60,8 +71,8 @@ impl HPF {
 }
 
 impl Module for HPF {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -80,4 +91,11 @@ impl Module for HPF {
         }
         self.x_prime.clone_from_slice(&input[..channels]);
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.x_prime.clone_from_slice(&previous.x_prime);
+        }
+    }
 }
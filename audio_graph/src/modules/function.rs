@@ -3,9 +3,15 @@
 //! Fn*N* modules allow to use regular numeric functions to transform input of *N* sources.
 //!
 //! Sources to connect: *N*, one for each argument of pure function.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 
+pub const FN1_INPUTS: &[Port] = &[Port::new("x")];
+
+pub const FN2_INPUTS: &[Port] = &[Port::new("x"), Port::new("y")];
+
+pub const FN3_INPUTS: &[Port] = &[Port::new("x"), Port::new("y"), Port::new("z")];
+
 pub struct Fn1 {
     ys: Vec<Sample>,
     f: fn(Sample) -> Sample,
@@ -21,8 +27,8 @@ impl Fn1 {
 }
 
 impl Module for Fn1 {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        FN1_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -34,6 +40,15 @@ impl Module for Fn1 {
             self.ys[i] = (self.f)(input[i]);
         }
     }
+
+    fn process_block(&mut self, input: &[Sample], output: &mut [Sample], frames: usize) {
+        for (y, x) in output.iter_mut().zip(input) {
+            *y = (self.f)(*x);
+        }
+        let channels = self.ys.len();
+        self.ys
+            .clone_from_slice(&output[(frames - 1) * channels..frames * channels]);
+    }
 }
 
 pub struct Fn2 {
@@ -51,8 +66,8 @@ impl Fn2 {
 }
 
 impl Module for Fn2 {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        FN2_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -65,6 +80,17 @@ impl Module for Fn2 {
             self.ys[i] = (self.f)(input[i], input[i + channels]);
         }
     }
+
+    fn process_block(&mut self, input: &[Sample], output: &mut [Sample], frames: usize) {
+        let channels = self.ys.len();
+        for (ys, xs) in output.chunks_mut(channels).zip(input.chunks(2 * channels)) {
+            for i in 0..channels {
+                ys[i] = (self.f)(xs[i], xs[i + channels]);
+            }
+        }
+        self.ys
+            .clone_from_slice(&output[(frames - 1) * channels..frames * channels]);
+    }
 }
 
 pub struct Fn3 {
@@ -82,8 +108,8 @@ impl Fn3 {
 }
 
 impl Module for Fn3 {
-    fn inputs(&self) -> u8 {
-        3
+    fn input_ports(&self) -> &[Port] {
+        FN3_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -96,4 +122,15 @@ impl Module for Fn3 {
             self.ys[i] = (self.f)(input[i], input[i + channels], input[i + 2 * channels]);
         }
     }
+
+    fn process_block(&mut self, input: &[Sample], output: &mut [Sample], frames: usize) {
+        let channels = self.ys.len();
+        for (ys, xs) in output.chunks_mut(channels).zip(input.chunks(3 * channels)) {
+            for i in 0..channels {
+                ys[i] = (self.f)(xs[i], xs[i + channels], xs[i + 2 * channels]);
+            }
+        }
+        self.ys
+            .clone_from_slice(&output[(frames - 1) * channels..frames * channels]);
+    }
 }
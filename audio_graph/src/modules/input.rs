@@ -20,10 +20,6 @@ impl Input {
 }
 
 impl Module for Input {
-    fn inputs(&self) -> u8 {
-        0
-    }
-
     fn output(&self) -> &Frame {
         &self.output
     }
@@ -1,6 +1,18 @@
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 
+pub const METRO_INPUTS: &[Port] = &[Port::new("freq").unit("Hz").range(0.0, 100.0)];
+
+pub const DMETRO_INPUTS: &[Port] = &[Port::new("period").unit("s").range(0.0, 60.0)];
+
+pub const BEAT_METRO_INPUTS: &[Port] = &[
+    Port::new("beat").unit("beats"),
+    Port::new("division")
+        .unit("beats")
+        .range(0.0, 64.0)
+        .default(1.0),
+];
+
 pub struct Metro {
     output: Vec<Sample>,
     last_trigger: Vec<u64>,
@@ -20,8 +32,8 @@ impl Metro {
 }
 
 impl Module for Metro {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        METRO_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -46,6 +58,14 @@ impl Module for Metro {
         }
         self.frame_number += 1;
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.last_trigger.clone_from_slice(&previous.last_trigger);
+            self.frame_number = previous.frame_number;
+        }
+    }
 }
 
 pub struct DMetro {
@@ -67,8 +87,8 @@ impl DMetro {
 }
 
 impl Module for DMetro {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        DMETRO_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -93,6 +113,14 @@ impl Module for DMetro {
         }
         self.frame_number += 1;
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.last_trigger.clone_from_slice(&previous.last_trigger);
+            self.frame_number = previous.frame_number;
+        }
+    }
 }
 
 pub struct MetroHold {
@@ -116,8 +144,8 @@ impl MetroHold {
 }
 
 impl Module for MetroHold {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        METRO_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -147,6 +175,15 @@ impl Module for MetroHold {
         }
         self.frame_number += 1;
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.last_trigger.clone_from_slice(&previous.last_trigger);
+            self.frequencies.clone_from_slice(&previous.frequencies);
+            self.frame_number = previous.frame_number;
+        }
+    }
 }
 
 pub struct DMetroHold {
@@ -170,8 +207,8 @@ impl DMetroHold {
 }
 
 impl Module for DMetroHold {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        DMETRO_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -201,4 +238,76 @@ impl Module for DMetroHold {
         }
         self.frame_number += 1;
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.last_trigger.clone_from_slice(&previous.last_trigger);
+            self.dts.clone_from_slice(&previous.dts);
+            self.frame_number = previous.frame_number;
+        }
+    }
+}
+
+/// Metro which follows a musical position, e.g. host transport, instead of its own clock.
+/// It triggers whenever `beat` crosses a multiple of `division`, so triggers stay aligned to the
+/// grid when position jumps or tempo changes, and there are none while position stands still.
+pub struct BeatMetro {
+    output: Vec<Sample>,
+    /// Index of the division where `beat` was on the previous frame, NaN before the first one.
+    last_division: Vec<Sample>,
+}
+
+impl BeatMetro {
+    pub fn new(channels: usize) -> Self {
+        BeatMetro {
+            output: vec![0.0; channels],
+            last_division: vec![Sample::NAN; channels],
+        }
+    }
+}
+
+impl Module for BeatMetro {
+    fn input_ports(&self) -> &[Port] {
+        BEAT_METRO_INPUTS
+    }
+
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, input: &Frame) {
+        let channels = self.output.len();
+        for (channel, (output, last_division)) in self
+            .output
+            .iter_mut()
+            .zip(self.last_division.iter_mut())
+            .enumerate()
+        {
+            let beat = input[channel];
+            let division = input[channels + channel];
+            if division <= 0.0 {
+                *output = 0.0;
+                continue;
+            }
+            let position = beat / division;
+            let index = position.floor();
+            // The very first frame triggers only if it is exactly on the grid, e.g. when playback
+            // starts from the beginning of a bar.
+            let trigger = if last_division.is_nan() {
+                index == position
+            } else {
+                index != *last_division
+            };
+            *output = if trigger { 1.0 } else { 0.0 };
+            *last_division = index;
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.last_division.clone_from_slice(&previous.last_division);
+        }
+    }
 }
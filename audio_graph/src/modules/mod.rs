@@ -1,13 +1,16 @@
 //! # Modules
 //!
 //! Various implementations of Module trait.
+pub mod band_limited;
 pub mod biquad;
 pub mod constant;
 pub mod delay;
+pub mod envelope;
 pub mod feedback;
 pub mod filter;
 pub mod function;
 pub mod input;
+pub mod ladder;
 pub mod metro;
 pub mod noise;
 pub mod osc;
@@ -17,5 +20,7 @@ pub mod phasor;
 pub mod prelude;
 pub mod pulse;
 pub mod sample_and_hold;
+pub mod svf;
+pub mod unit_delay;
 pub mod yin;
 pub mod zip;
@@ -1,37 +1,251 @@
 //! # Noise
 //!
-//! White noise.
+//! White, pink, brown and velvet noise.
 //!
-//! Sources to connect: none required.
-use crate::module::Module;
+//! Each channel gets its own independent stream, unless noise is correlated, then all channels
+//! output the same values.
+//!
+//! Sources to connect: none required, density for velvet noise.
+use crate::module::{Module, Port};
+use crate::random::Random;
 use crate::sample::{Frame, Sample};
-use rand::{self, Rng};
+
+pub const VELVET_INPUTS: &[Port] = &[Port::new("density")
+    .unit("Hz")
+    .range(0.0, 20_000.0)
+    .default(2000.0)];
+
+/// Generator per stream: per channel, or a single one if noise is correlated.
+fn generators(channels: usize, seed: u64, correlated: bool) -> Vec<Random> {
+    let mut seeds = Random::new(seed);
+    let streams = if correlated { 1 } else { channels };
+    (0..streams)
+        .map(|_| Random::new(seeds.next_u64()))
+        .collect()
+}
+
+/// Copy the single stream of correlated noise, which is written to the first channel, to the
+/// rest of channels.
+fn spread(values: &mut [Sample], streams: usize) {
+    if streams == 1 {
+        let x = values[0];
+        for value in values[1..].iter_mut() {
+            *value = x;
+        }
+    }
+}
 
 pub struct Noise {
+    generators: Vec<Random>,
     values: Vec<Sample>,
 }
 
 impl Noise {
-    pub fn new(channels: usize) -> Self {
+    pub fn new(channels: usize, seed: u64, correlated: bool) -> Self {
         Noise {
+            generators: generators(channels, seed, correlated),
             values: vec![0.0; channels],
         }
     }
 }
 
 impl Module for Noise {
-    fn inputs(&self) -> u8 {
-        0
+    fn output(&self) -> &Frame {
+        &self.values
+    }
+
+    fn sample(&mut self, _input: &Frame) {
+        for (value, generator) in self.values.iter_mut().zip(self.generators.iter_mut()) {
+            *value = generator.bipolar();
+        }
+        spread(&mut self.values, self.generators.len());
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            if previous.generators.len() == self.generators.len() {
+                self.generators.clone_from_slice(&previous.generators);
+            }
+        }
+    }
+}
+
+/// Pink (-3 dB/octave) noise: white noise through Paul Kellet's refined filter.
+pub struct PinkNoise {
+    generators: Vec<Random>,
+    filters: Vec<[Sample; 7]>,
+    values: Vec<Sample>,
+}
+
+impl PinkNoise {
+    pub fn new(channels: usize, seed: u64, correlated: bool) -> Self {
+        let generators = generators(channels, seed, correlated);
+        PinkNoise {
+            filters: vec![[0.0; 7]; generators.len()],
+            generators,
+            values: vec![0.0; channels],
+        }
+    }
+}
+
+impl Module for PinkNoise {
+    fn output(&self) -> &Frame {
+        &self.values
+    }
+
+    fn sample(&mut self, _input: &Frame) {
+        for ((value, generator), b) in self
+            .values
+            .iter_mut()
+            .zip(self.generators.iter_mut())
+            .zip(self.filters.iter_mut())
+        {
+            let white = generator.bipolar();
+            b[0] = 0.99886 * b[0] + white * 0.055_517_9;
+            b[1] = 0.99332 * b[1] + white * 0.075_075_9;
+            b[2] = 0.96900 * b[2] + white * 0.153_852;
+            b[3] = 0.86650 * b[3] + white * 0.310_485_6;
+            b[4] = 0.55000 * b[4] + white * 0.532_952_2;
+            b[5] = -0.7616 * b[5] - white * 0.016_898;
+            let pink = b.iter().sum::<Sample>() + white * 0.5362;
+            b[6] = white * 0.115_926;
+            // Bring the output roughly to -1..1.
+            *value = pink * 0.11;
+        }
+        spread(&mut self.values, self.generators.len());
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            if previous.generators.len() == self.generators.len() {
+                self.generators.clone_from_slice(&previous.generators);
+                self.filters.clone_from_slice(&previous.filters);
+            }
+        }
+    }
+}
+
+/// Brown (-6 dB/octave) noise: white noise through leaky integrator.
+pub struct BrownNoise {
+    generators: Vec<Random>,
+    integrals: Vec<Sample>,
+    values: Vec<Sample>,
+}
+
+impl BrownNoise {
+    pub fn new(channels: usize, seed: u64, correlated: bool) -> Self {
+        let generators = generators(channels, seed, correlated);
+        BrownNoise {
+            integrals: vec![0.0; generators.len()],
+            generators,
+            values: vec![0.0; channels],
+        }
     }
+}
 
+impl Module for BrownNoise {
     fn output(&self) -> &Frame {
         &self.values
     }
 
     fn sample(&mut self, _input: &Frame) {
-        let mut rng = rand::thread_rng();
-        for value in self.values.iter_mut() {
-            *value = rng.gen_range(-1.0, 1.0);
+        for ((value, generator), integral) in self
+            .values
+            .iter_mut()
+            .zip(self.generators.iter_mut())
+            .zip(self.integrals.iter_mut())
+        {
+            *integral = (*integral + 0.02 * generator.bipolar()) / 1.02;
+            // Bring the output roughly to -1..1.
+            *value = 3.5 * *integral;
+        }
+        spread(&mut self.values, self.generators.len());
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            if previous.generators.len() == self.generators.len() {
+                self.generators.clone_from_slice(&previous.generators);
+                self.integrals.clone_from_slice(&previous.integrals);
+            }
+        }
+    }
+}
+
+/// Velvet noise: impulses of random sign, one at a random position within every period of
+/// 1/density seconds, zeros elsewhere.
+pub struct VelvetNoise {
+    generators: Vec<Random>,
+    /// Per stream: position within the current period, 0..1, where the impulse is, and whether
+    /// it was already emitted.
+    periods: Vec<(Sample, Sample, bool)>,
+    sample_rate: Sample,
+    values: Vec<Sample>,
+}
+
+impl VelvetNoise {
+    pub fn new(channels: usize, sample_rate: usize, seed: u64, correlated: bool) -> Self {
+        let mut generators = generators(channels, seed, correlated);
+        let periods = generators
+            .iter_mut()
+            .map(|generator| (0.0, generator.unit(), false))
+            .collect();
+        VelvetNoise {
+            generators,
+            periods,
+            sample_rate: sample_rate as Sample,
+            values: vec![0.0; channels],
+        }
+    }
+}
+
+impl Module for VelvetNoise {
+    fn input_ports(&self) -> &[Port] {
+        VELVET_INPUTS
+    }
+
+    fn output(&self) -> &Frame {
+        &self.values
+    }
+
+    fn sample(&mut self, input: &Frame) {
+        for (((value, generator), period), density) in self
+            .values
+            .iter_mut()
+            .zip(self.generators.iter_mut())
+            .zip(self.periods.iter_mut())
+            .zip(input)
+        {
+            let (position, impulse, emitted) = period;
+            if *position >= 1.0 {
+                *position -= position.floor();
+                *impulse = generator.unit();
+                *emitted = false;
+            }
+            let step = density.max(0.0) / self.sample_rate;
+            // The last frame of the period is at 1 - step or later, scaling the impulse position
+            // to it makes sure the impulse is not skipped by wrapping.
+            *value = if !*emitted && *position >= *impulse * (1.0 - step) {
+                *emitted = true;
+                if generator.unit() < 0.5 {
+                    -1.0
+                } else {
+                    1.0
+                }
+            } else {
+                0.0
+            };
+            *position += step;
+        }
+        spread(&mut self.values, self.generators.len());
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            if previous.generators.len() == self.generators.len() {
+                self.generators.clone_from_slice(&previous.generators);
+                self.periods.clone_from_slice(&previous.periods);
+            }
         }
     }
 }
@@ -1,11 +1,18 @@
 //! # Oscillator
 //!
 //! Sources to connect: frequency.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::modules::function::Fn1;
 use crate::modules::phasor::{Phasor, Phasor0};
 use crate::sample::{Frame, Sample};
 
+pub const OSC_INPUTS: &[Port] = &[Port::new("freq").unit("Hz").range(0.0, 20_000.0)];
+
+pub const OSC_PHASE_INPUTS: &[Port] = &[
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("phase").range(-1.0, 1.0).default(0.0),
+];
+
 pub struct Osc {
     phasor: Phasor,
     osc: Fn1,
@@ -20,8 +27,8 @@ impl Osc {
 }
 
 impl Module for Osc {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        OSC_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -32,6 +39,12 @@ impl Module for Osc {
         self.phasor.sample(input);
         self.osc.sample(self.phasor.output());
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any_mut().downcast_mut::<Self>() {
+            self.phasor.transplant(&mut previous.phasor);
+        }
+    }
 }
 
 pub struct OscPhase {
@@ -48,8 +61,8 @@ impl OscPhase {
 }
 
 impl Module for OscPhase {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        OSC_PHASE_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -60,4 +73,10 @@ impl Module for OscPhase {
         self.phasor.sample(input);
         self.osc.sample(self.phasor.output());
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any_mut().downcast_mut::<Self>() {
+            self.phasor.transplant(&mut previous.phasor);
+        }
+    }
 }
@@ -1,10 +1,21 @@
 //! # Stereo panner
 //!
 //! Sources to connect: left, right, position.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::pure;
 use crate::sample::{Frame, Sample};
 
+pub const PAN1_INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("position").range(-1.0, 1.0).default(0.0),
+];
+
+pub const PAN2_INPUTS: &[Port] = &[
+    Port::new("left").range(-1.0, 1.0),
+    Port::new("right").range(-1.0, 1.0),
+    Port::new("position").range(-1.0, 1.0).default(0.0),
+];
+
 pub struct Pan1 {
     channels: usize,
     output: Vec<Sample>,
@@ -20,8 +31,8 @@ impl Pan1 {
 }
 
 impl Module for Pan1 {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        PAN1_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -50,8 +61,8 @@ impl Pan2 {
 }
 
 impl Module for Pan2 {
-    fn inputs(&self) -> u8 {
-        3
+    fn input_ports(&self) -> &[Port] {
+        PAN2_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -84,8 +95,8 @@ impl Pan3 {
 }
 
 impl Module for Pan3 {
-    fn inputs(&self) -> u8 {
-        3
+    fn input_ports(&self) -> &[Port] {
+        PAN2_INPUTS
     }
 
     fn output(&self) -> &Frame {
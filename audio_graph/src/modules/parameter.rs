@@ -22,10 +22,6 @@ impl Parameter {
 }
 
 impl Module for Parameter {
-    fn inputs(&self) -> u8 {
-        0
-    }
-
     fn output(&self) -> &Frame {
         &self.output
     }
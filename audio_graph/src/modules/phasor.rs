@@ -1,6 +1,6 @@
 //! # Phasor
 //!
-//! ```
+//! ```text
 //!  1     /|    /|    /|    /|
 //!       / |   / |   / |   / |
 //!  0   /  |  /  |  /  |  /  |
@@ -16,9 +16,16 @@
 //! themselves anymore.
 //!
 //! Sources to connect: frequency.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 
+pub const PHASOR_INPUTS: &[Port] = &[Port::new("freq").unit("Hz").range(0.0, 20_000.0)];
+
+pub const PHASOR0_INPUTS: &[Port] = &[
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("phase").range(-1.0, 1.0).default(0.0),
+];
+
 pub struct Phasor {
     phases: Vec<Sample>,
     sample_rate: Sample,
@@ -34,8 +41,8 @@ impl Phasor {
 }
 
 impl Module for Phasor {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        PHASOR_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -45,7 +52,13 @@ impl Module for Phasor {
     fn sample(&mut self, input: &Frame) {
         for (phase, frequency) in self.phases.iter_mut().zip(input.iter()) {
             let dx = frequency / self.sample_rate;
-            *phase = ((*phase + dx + 1.0) % 2.0) - 1.0;;
+            *phase = ((*phase + dx + 1.0) % 2.0) - 1.0;
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.phases.clone_from_slice(&previous.phases);
         }
     }
 }
@@ -65,8 +78,8 @@ impl Phasor0 {
 }
 
 impl Module for Phasor0 {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        PHASOR0_INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -79,7 +92,13 @@ impl Module for Phasor0 {
             let frequency = input[channel];
             let phase0 = input[channel + channels];
             let dx = frequency / self.sample_rate;
-            *phase = ((*phase + phase0 + dx + 1.0) % 2.0) - 1.0;;
+            *phase = ((*phase + phase0 + dx + 1.0) % 2.0) - 1.0;
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.phases.clone_from_slice(&previous.phases);
         }
     }
 }
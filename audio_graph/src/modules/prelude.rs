@@ -1,20 +1,29 @@
 //! # Modules prelude
 //!
 //! Essentially is a re-export of all modules.
-pub use crate::modules::biquad::{make_hpf_coefficients, make_lpf_coefficients, BiQuad};
+pub use crate::modules::band_limited::{BandLimited, Waveform};
+pub use crate::modules::biquad::{
+    make_allpass_coefficients, make_bpf_peak_coefficients, make_bpf_skirt_coefficients,
+    make_high_shelf_coefficients, make_hpf_coefficients, make_low_shelf_coefficients,
+    make_lpf_coefficients, make_notch_coefficients, make_peak_coefficients, BiQuad,
+};
 pub use crate::modules::constant::Constant;
 pub use crate::modules::delay::Delay;
+pub use crate::modules::envelope::{Curve, Envelope, Shape};
 pub use crate::modules::feedback::Feedback;
 pub use crate::modules::filter::{HPF, LPF};
 pub use crate::modules::function::{Fn1, Fn2, Fn3};
 pub use crate::modules::input::Input;
-pub use crate::modules::metro::{DMetro, DMetroHold, Metro, MetroHold};
-pub use crate::modules::noise::Noise;
+pub use crate::modules::ladder::Ladder;
+pub use crate::modules::metro::{BeatMetro, DMetro, DMetroHold, Metro, MetroHold};
+pub use crate::modules::noise::{BrownNoise, Noise, PinkNoise, VelvetNoise};
 pub use crate::modules::osc::{Osc, OscPhase};
 pub use crate::modules::pan::{Pan1, Pan2, Pan3};
 pub use crate::modules::parameter::Parameter;
 pub use crate::modules::phasor::{Phasor, Phasor0};
 pub use crate::modules::pulse::Pulse;
 pub use crate::modules::sample_and_hold::SampleAndHold;
+pub use crate::modules::svf::{FilterMode, StateVariable};
+pub use crate::modules::unit_delay::{UnitDelayCell, UnitDelayReceive, UnitDelaySend};
 pub use crate::modules::yin::Yin;
 pub use crate::modules::zip::Zip;
@@ -1,12 +1,17 @@
 //! # Pulse wave
 //!
 //! Sources to connect: frequency, duty cycle.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::modules::function::Fn2;
 use crate::modules::phasor::Phasor;
 use crate::pure::rectangle;
 use crate::sample::{Frame, Sample};
 
+pub const INPUTS: &[Port] = &[
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("width").range(0.0, 1.0).default(0.5),
+];
+
 pub struct Pulse {
     channels: usize,
     input: Vec<Sample>,
@@ -29,8 +34,8 @@ impl Pulse {
 }
 
 impl Module for Pulse {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -43,4 +48,10 @@ impl Module for Pulse {
         self.input[self.channels..].clone_from_slice(&input[self.channels..(2 * self.channels)]);
         self.osc.sample(&self.input);
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any_mut().downcast_mut::<Self>() {
+            self.phasor.transplant(&mut previous.phasor);
+        }
+    }
 }
@@ -1,9 +1,14 @@
 //! Sample & Hold
 //!
 //! Sources to connect: trigger, input.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 
+pub const INPUTS: &[Port] = &[
+    Port::new("trigger").range(0.0, 1.0),
+    Port::new("input").range(-1.0, 1.0),
+];
+
 pub struct SampleAndHold {
     output: Vec<Sample>,
 }
@@ -17,8 +22,8 @@ impl SampleAndHold {
 }
 
 impl Module for SampleAndHold {
-    fn inputs(&self) -> u8 {
-        2
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -33,4 +38,10 @@ impl Module for SampleAndHold {
             *output = *output * (1.0 - t) + x * t
         }
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+        }
+    }
 }
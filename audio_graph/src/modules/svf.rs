@@ -0,0 +1,98 @@
+//! # State-variable filter
+//!
+//! Trapezoidal (TPT) state-variable filter after Vadim Zavalishin's "The Art of VA Filter
+//! Design", in Andrew Simper's formulation. Unlike `BiQuad`, it stays stable when cut-off
+//! frequency is modulated at audio rate.
+//!
+//! Low-pass, band-pass, high-pass and notch responses are computed at once, the module outputs
+//! the selected one.
+//!
+//! Sources to connect: input, cut-off frequency, Q.
+use crate::module::{Module, Port};
+use crate::pure::prewarp;
+use crate::sample::{Frame, Sample};
+
+pub const INPUTS: &[Port] = &[
+    Port::new("input").range(-1.0, 1.0),
+    Port::new("freq").unit("Hz").range(0.0, 20_000.0),
+    Port::new("q").range(0.0, 10.0).default(0.707),
+];
+
+/// Lowest Q, to keep damping finite.
+const MIN_Q: Sample = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    LowPass,
+    BandPass,
+    HighPass,
+    Notch,
+}
+
+pub struct StateVariable {
+    mode: FilterMode,
+    output: Vec<Sample>,
+    sample_rate: Sample,
+    /// States of the two integrators per channel.
+    ic1: Vec<Sample>,
+    ic2: Vec<Sample>,
+}
+
+impl StateVariable {
+    pub fn new(channels: usize, sample_rate: usize, mode: FilterMode) -> Self {
+        StateVariable {
+            mode,
+            output: vec![0.0; channels],
+            sample_rate: sample_rate as Sample,
+            ic1: vec![0.0; channels],
+            ic2: vec![0.0; channels],
+        }
+    }
+}
+
+impl Module for StateVariable {
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
+    }
+
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, input: &Frame) {
+        let channels = self.output.len();
+        for channel in 0..channels {
+            let x = input[channel];
+            let q = input[channel + 2 * channels].max(MIN_Q);
+
+            let g = prewarp(input[channel + channels], self.sample_rate);
+            let k = 1.0 / q;
+            let a1 = 1.0 / (1.0 + g * (g + k));
+            let a2 = g * a1;
+            let a3 = g * a2;
+
+            let ic1 = self.ic1[channel];
+            let ic2 = self.ic2[channel];
+            let v3 = x - ic2;
+            let band = a1 * ic1 + a2 * v3;
+            let low = ic2 + a2 * ic1 + a3 * v3;
+            self.ic1[channel] = 2.0 * band - ic1;
+            self.ic2[channel] = 2.0 * low - ic2;
+
+            self.output[channel] = match self.mode {
+                FilterMode::LowPass => low,
+                FilterMode::BandPass => band,
+                FilterMode::HighPass => x - k * band - low,
+                FilterMode::Notch => x - k * band,
+            };
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            self.ic1.clone_from_slice(&previous.ic1);
+            self.ic2.clone_from_slice(&previous.ic2);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! # Unit delay
+//!
+//! Pair of modules which pass a signal to the next frame, allowing to close feedback loops in
+//! otherwise acyclic AudioGraph.
+//!
+//! UnitDelaySend passes its input through and remembers it, UnitDelayReceive outputs what was
+//! remembered on the previous frame. They are not connected in the graph and communicate via
+//! shared cell, so the receive could be a source of the send without making a cycle.
+//!
+//! Sources to connect: input for send, none for receive.
+use crate::module::{Module, Port};
+use crate::sample::{Frame, Sample};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const SEND_INPUTS: &[Port] = &[Port::new("input")];
+
+/// Storage shared between send and receive.
+///
+/// It has two slots which are written and read on alternate frames, and both modules count
+/// frames on their own. Thus receive gets the previous frame of send regardless of which one
+/// is sampled first.
+#[derive(Clone)]
+pub struct UnitDelayCell {
+    channels: usize,
+    /// Samples are stored as bits because there is no AtomicF64.
+    slots: Arc<Vec<AtomicU64>>,
+}
+
+impl UnitDelayCell {
+    pub fn new(channels: usize) -> Self {
+        UnitDelayCell {
+            channels,
+            slots: Arc::new((0..2 * channels).map(|_| AtomicU64::new(0)).collect()),
+        }
+    }
+
+    fn slot(&self, frame_number: u64) -> &[AtomicU64] {
+        let offset = (frame_number % 2) as usize * self.channels;
+        &self.slots[offset..(offset + self.channels)]
+    }
+}
+
+pub struct UnitDelaySend {
+    cell: UnitDelayCell,
+    frame_number: u64,
+    output: Vec<Sample>,
+}
+
+impl UnitDelaySend {
+    pub fn new(cell: UnitDelayCell) -> Self {
+        let channels = cell.channels;
+        UnitDelaySend {
+            cell,
+            frame_number: 0,
+            output: vec![0.0; channels],
+        }
+    }
+}
+
+impl Module for UnitDelaySend {
+    fn input_ports(&self) -> &[Port] {
+        SEND_INPUTS
+    }
+
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, input: &Frame) {
+        let slot = self.cell.slot(self.frame_number);
+        for ((output, x), cell) in self.output.iter_mut().zip(input).zip(slot) {
+            *output = *x;
+            cell.store(x.to_bits(), Ordering::Relaxed);
+        }
+        self.frame_number += 1;
+    }
+
+    fn per_frame(&self) -> bool {
+        true
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+            // Receive reads the slot of the frame before the first one, so the loop continues
+            // with the last value sent by the previous incarnation.
+            for (cell, x) in self.cell.slot(1).iter().zip(&self.output) {
+                cell.store(x.to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+pub struct UnitDelayReceive {
+    cell: UnitDelayCell,
+    frame_number: u64,
+    output: Vec<Sample>,
+}
+
+impl UnitDelayReceive {
+    pub fn new(cell: UnitDelayCell) -> Self {
+        let channels = cell.channels;
+        UnitDelayReceive {
+            cell,
+            frame_number: 0,
+            output: vec![0.0; channels],
+        }
+    }
+}
+
+impl Module for UnitDelayReceive {
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, _input: &Frame) {
+        // Slot of the previous frame.
+        let slot = self.cell.slot(self.frame_number + 1);
+        for (output, cell) in self.output.iter_mut().zip(slot) {
+            *output = Sample::from_bits(cell.load(Ordering::Relaxed));
+        }
+        self.frame_number += 1;
+    }
+
+    fn per_frame(&self) -> bool {
+        true
+    }
+}
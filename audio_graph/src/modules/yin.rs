@@ -3,10 +3,12 @@
 //! Yin pitch detection algorithm.
 //!
 //! Sources to connect: signal to detect pitch of.
-use crate::module::Module;
+use crate::module::{Module, Port};
 use crate::sample::{Frame, Sample};
 use std::collections::VecDeque;
 
+pub const INPUTS: &[Port] = &[Port::new("input").range(-1.0, 1.0)];
+
 pub struct Yin {
     buffer: Vec<Sample>,
     channels: usize,
@@ -106,8 +108,8 @@ impl Yin {
 }
 
 impl Module for Yin {
-    fn inputs(&self) -> u8 {
-        1
+    fn input_ports(&self) -> &[Port] {
+        INPUTS
     }
 
     fn output(&self) -> &Frame {
@@ -133,4 +135,18 @@ impl Module for Yin {
         }
         self.frame_number += 1;
     }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            if previous.buffer.len() == self.buffer.len() {
+                // Windows have the same capacity, so refilling them doesn't allocate.
+                for (window, previous) in self.windows.iter_mut().zip(&previous.windows) {
+                    window.clear();
+                    window.extend(previous);
+                }
+                self.output.clone_from_slice(&previous.output);
+                self.frame_number = previous.frame_number;
+            }
+        }
+    }
 }
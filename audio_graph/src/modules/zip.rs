@@ -21,8 +21,8 @@ impl Zip {
 }
 
 impl Module for Zip {
-    fn inputs(&self) -> u8 {
-        self.channels as u8
+    fn inputs(&self) -> usize {
+        self.channels
     }
 
     fn output(&self) -> &Frame {
@@ -110,6 +110,12 @@ pub fn midi2freq(x: Sample) -> Sample {
     440.0 * 2.0f64.powf((x - 69.0) / 12.0)
 }
 
+/// Prewarped gain of a trapezoidal integrator for cut-off `freq`, as in TPT filters.
+/// Cut-off is kept below Nyquist frequency, where the gain goes to infinity.
+pub fn prewarp(freq: Sample, sample_rate: Sample) -> Sample {
+    (PI * freq.clamp(0.0, 0.49 * sample_rate) / sample_rate).tan()
+}
+
 /// Stereo intensity-preserving panner
 pub fn pan(l: Sample, r: Sample, c: Sample) -> (Sample, Sample) {
     (
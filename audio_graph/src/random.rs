@@ -0,0 +1,45 @@
+//! # Random
+//!
+//! Fast seeded pseudo-random generator for noise modules.
+//!
+//! It is SplitMix64: the state is a single u64, any seed is fine, and quality is way more than
+//! enough for audio. Generators are seeded explicitly, so rendering the same program twice gives
+//! the same sound.
+use crate::sample::Sample;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in 0..1.
+    pub fn unit(&mut self) -> Sample {
+        (self.next_u64() >> 11) as Sample / (1u64 << 53) as Sample
+    }
+
+    /// Uniformly distributed in -1..1.
+    pub fn bipolar(&mut self) -> Sample {
+        2.0 * self.unit() - 1.0
+    }
+}
+
+/// Seed for generators which were not given one explicitly. Seeds differ on every call, so such
+/// generators are not correlated, but the sequence is the same in every run of the program.
+pub fn next_seed() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    Random::new(COUNTER.fetch_add(1, Ordering::Relaxed)).next_u64()
+}
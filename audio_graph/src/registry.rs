@@ -0,0 +1,721 @@
+//! # Module registry
+//!
+//! Table of modules known to the stack language. Each entry lists module's names, inputs, a
+//! constructor and a one-line description. The compiler, help output and editor autocompletion
+//! all read from this table, so registering a new `Module` implementation (from this crate or a
+//! third-party one) makes it available everywhere at once.
+//!
+//! Tokens may carry colon-separated arguments which are passed to the constructor, e.g. `param:3`
+//! is looked up as `param` and constructed with `["3"]`.
+use crate::graph::Node;
+use crate::module::Port;
+use crate::modules::prelude::*;
+use crate::modules::{
+    band_limited, biquad, delay, envelope, feedback, filter, function, ladder, metro, noise, osc,
+    pan, phasor, pulse, sample_and_hold, svf, yin,
+};
+use crate::pure::*;
+use crate::random::next_seed;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Build a module given channels count, sample rate, number of external parameters and token
+/// arguments. Constructors are closures, so modules could share state with whoever registered them.
+pub type Constructor =
+    Box<dyn Fn(usize, usize, usize, &[&str]) -> Result<Node, String> + Send + Sync>;
+
+/// Sources module expects to find on the stack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Inputs {
+    /// Named inputs, the same ports module returns from `Module::input_ports`.
+    Ports(&'static [Port]),
+    /// One source per channel, like in Zip.
+    Channels,
+}
+
+impl fmt::Display for Inputs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inputs::Ports(ports) => write!(f, "{}", ports.len()),
+            Inputs::Channels => write!(f, "channels"),
+        }
+    }
+}
+
+pub struct Entry {
+    /// The first name is the canonical one, the rest are aliases.
+    pub names: &'static [&'static str],
+    pub inputs: Inputs,
+    pub doc: &'static str,
+    pub constructor: Constructor,
+}
+
+pub struct ModuleRegistry {
+    entries: Vec<Entry>,
+    /// Name or alias -> position in `entries`.
+    index: HashMap<&'static str, usize>,
+}
+
+impl ModuleRegistry {
+    /// Create an empty registry. Use `ModuleRegistry::default()` to get one with built-in modules.
+    pub fn new() -> Self {
+        ModuleRegistry {
+            entries: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Register module under the given names.
+    /// Names which are already taken are re-assigned to the new module.
+    pub fn register<F>(
+        &mut self,
+        names: &'static [&'static str],
+        inputs: Inputs,
+        doc: &'static str,
+        constructor: F,
+    ) where
+        F: Fn(usize, usize, usize, &[&str]) -> Result<Node, String> + Send + Sync + 'static,
+    {
+        let position = self.entries.len();
+        for name in names {
+            self.index.insert(name, position);
+        }
+        self.entries.push(Entry {
+            names,
+            inputs,
+            doc,
+            constructor: Box::new(constructor),
+        });
+    }
+
+    /// Find entry by module name or alias.
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.index
+            .get(name)
+            .map(|&position| &self.entries[position])
+    }
+
+    /// Construct module for the token (name with optional colon-separated arguments).
+    /// Return `None` if there is no such module.
+    pub fn construct(
+        &self,
+        token: &str,
+        channels: usize,
+        sample_rate: usize,
+        parameters: usize,
+    ) -> Option<Result<Node, String>> {
+        let mut parts = token.split(':');
+        let name = parts.next()?;
+        let args = parts.collect::<Vec<_>>();
+        self.get(name)
+            .map(|entry| (entry.constructor)(channels, sample_rate, parameters, &args))
+    }
+
+    /// All registered entries in registration order.
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        // Hide entries which were completely overridden by later registrations.
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(position, entry)| {
+                entry
+                    .names
+                    .iter()
+                    .any(|name| self.index.get(name) == Some(position))
+            })
+            .map(|(_, entry)| entry)
+    }
+
+    /// All names and aliases, sorted. Useful for autocompletion.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = self.index.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    /// Stack effect of the module in Forth notation, e.g. `bqlpf ( input freq q=0.707 -- out )`.
+    /// Inputs with default values are marked with them.
+    /// Return `None` if there is no such module.
+    ///
+    /// It only reads the registry and doesn't construct the module, so it is cheap enough to
+    /// call on every keystroke.
+    pub fn signature(&self, token: &str) -> Option<String> {
+        let entry = self.get(token.split(':').next()?)?;
+        let inputs = match entry.inputs {
+            Inputs::Ports(ports) => ports
+                .iter()
+                .map(|port| match port.default {
+                    Some(x) => format!("{}={}", port.name, x),
+                    None => port.name.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            Inputs::Channels => "channels inputs".to_string(),
+        };
+        if inputs.is_empty() {
+            Some(format!("{} ( -- out )", token))
+        } else {
+            Some(format!("{} ( {} -- out )", token, inputs))
+        }
+    }
+
+    /// Human-readable list of modules, one per line.
+    pub fn help(&self) -> String {
+        let mut help = String::new();
+        for entry in self.entries() {
+            help.push_str(&format!(
+                "{} ({}): {}\n",
+                entry.names.join(", "),
+                entry.inputs,
+                entry.doc
+            ));
+        }
+        help
+    }
+}
+
+impl Default for ModuleRegistry {
+    /// Registry with all modules of this crate.
+    fn default() -> Self {
+        let mut registry = ModuleRegistry::new();
+        register_builtins(&mut registry);
+        registry
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[&str], i: usize, what: &str) -> Result<T, String> {
+    match args.get(i) {
+        Some(arg) => arg
+            .parse::<T>()
+            .map_err(|_| format!("invalid {} `{}`", what, arg)),
+        None => Err(format!("missing {}", what)),
+    }
+}
+
+/// Maximum delay time in seconds of `delay:<max>`-like tokens. Buffer for the whole of it is
+/// allocated upfront, and polyphonic programs have one per voice, so the default is moderate.
+fn parse_max_delay(args: &[&str]) -> Result<f64, String> {
+    match args {
+        [] => Ok(10.0),
+        [_] => match parse_arg(args, 0, "maximum delay")? {
+            max if max > 0.0 && max <= 60.0 => Ok(max),
+            max => Err(format!(
+                "maximum delay {} is out of range, expected up to 60 seconds",
+                max
+            )),
+        },
+        _ => Err("expected optional maximum delay in seconds".into()),
+    }
+}
+
+/// Seed and whether channels are correlated for `noise:<seed>:correlated`-like tokens. Without
+/// seed every node gets a different one.
+fn parse_noise_args(args: &[&str]) -> Result<(u64, bool), String> {
+    let seed = match args.first() {
+        Some(_) => parse_arg(args, 0, "seed")?,
+        None => next_seed(),
+    };
+    let correlated = match args.get(1) {
+        None | Some(&"independent") => false,
+        Some(&"correlated") => true,
+        Some(arg) => {
+            return Err(format!(
+                "invalid mode `{}`, expected correlated or independent",
+                arg
+            ))
+        }
+    };
+    Ok((seed, correlated))
+}
+
+/// Curve and retrigger mode of `adsr:exp:hard`-like tokens, in any order. Defaults are linear
+/// curve and soft retrigger.
+fn parse_envelope_args(args: &[&str]) -> Result<(Curve, bool), String> {
+    let mut curve = Curve::Linear;
+    let mut hard_retrigger = false;
+    for arg in args {
+        match *arg {
+            "lin" => curve = Curve::Linear,
+            "exp" => curve = Curve::Exponential,
+            "soft" => hard_retrigger = false,
+            "hard" => hard_retrigger = true,
+            _ => {
+                return Err(format!(
+                    "invalid option `{}`, expected lin, exp, soft or hard",
+                    arg
+                ))
+            }
+        }
+    }
+    Ok((curve, hard_retrigger))
+}
+
+fn parse_svf_args(args: &[&str]) -> Result<FilterMode, String> {
+    match args {
+        [] | ["lp"] => Ok(FilterMode::LowPass),
+        ["bp"] => Ok(FilterMode::BandPass),
+        ["hp"] => Ok(FilterMode::HighPass),
+        ["notch"] => Ok(FilterMode::Notch),
+        _ => Err("expected one of lp, bp, hp or notch".to_string()),
+    }
+}
+
+fn register_builtins(r: &mut ModuleRegistry) {
+    use Inputs::*;
+
+    // Oscillators
+    r.register(
+        &["s"],
+        Ports(osc::OSC_INPUTS),
+        "sine wave: frequency",
+        |c, sr, _, _| Ok(Box::new(Osc::new(c, sr, sine))),
+    );
+    r.register(
+        &["sine"],
+        Ports(osc::OSC_PHASE_INPUTS),
+        "sine wave with phase modulation: frequency, phase",
+        |c, sr, _, _| Ok(Box::new(OscPhase::new(c, sr, sine))),
+    );
+    r.register(
+        &["t"],
+        Ports(osc::OSC_INPUTS),
+        "triangle wave: frequency",
+        |c, sr, _, _| Ok(Box::new(Osc::new(c, sr, triangle))),
+    );
+    r.register(
+        &["tri"],
+        Ports(osc::OSC_PHASE_INPUTS),
+        "triangle wave with phase modulation: frequency, phase",
+        |c, sr, _, _| Ok(Box::new(OscPhase::new(c, sr, triangle))),
+    );
+    r.register(
+        &["w"],
+        Ports(phasor::PHASOR_INPUTS),
+        "saw wave (phasor): frequency",
+        |c, sr, _, _| Ok(Box::new(Phasor::new(c, sr))),
+    );
+    r.register(
+        &["saw"],
+        Ports(phasor::PHASOR0_INPUTS),
+        "saw wave with phase modulation: frequency, phase",
+        |c, sr, _, _| Ok(Box::new(Phasor0::new(c, sr))),
+    );
+    r.register(
+        &["p", "pulse"],
+        Ports(pulse::INPUTS),
+        "pulse wave: frequency, duty cycle",
+        |c, sr, _, _| Ok(Box::new(Pulse::new(c, sr))),
+    );
+    r.register(
+        &["blsaw"],
+        Ports(band_limited::INPUTS),
+        "band-limited saw wave: frequency",
+        |c, sr, _, _| Ok(Box::new(BandLimited::new(c, sr, Waveform::Saw))),
+    );
+    r.register(
+        &["blsquare"],
+        Ports(band_limited::SQUARE_INPUTS),
+        "band-limited square wave: frequency, duty cycle",
+        |c, sr, _, _| Ok(Box::new(BandLimited::new(c, sr, Waveform::Square))),
+    );
+    r.register(
+        &["bltri"],
+        Ports(band_limited::INPUTS),
+        "band-limited triangle wave: frequency",
+        |c, sr, _, _| Ok(Box::new(BandLimited::new(c, sr, Waveform::Triangle))),
+    );
+    r.register(
+        &["n", "noise"],
+        Ports(&[]),
+        "white noise: noise, noise:<seed> or noise:<seed>:correlated",
+        |c, _, _, args| {
+            let (seed, correlated) = parse_noise_args(args)?;
+            Ok(Box::new(Noise::new(c, seed, correlated)))
+        },
+    );
+    r.register(
+        &["pink"],
+        Ports(&[]),
+        "pink noise: pink, pink:<seed> or pink:<seed>:correlated",
+        |c, _, _, args| {
+            let (seed, correlated) = parse_noise_args(args)?;
+            Ok(Box::new(PinkNoise::new(c, seed, correlated)))
+        },
+    );
+    r.register(
+        &["brown"],
+        Ports(&[]),
+        "brown noise: brown, brown:<seed> or brown:<seed>:correlated",
+        |c, _, _, args| {
+            let (seed, correlated) = parse_noise_args(args)?;
+            Ok(Box::new(BrownNoise::new(c, seed, correlated)))
+        },
+    );
+    r.register(
+        &["velvet"],
+        Ports(noise::VELVET_INPUTS),
+        "velvet noise: density; velvet:<seed> or velvet:<seed>:correlated",
+        |c, sr, _, args| {
+            let (seed, correlated) = parse_noise_args(args)?;
+            Ok(Box::new(VelvetNoise::new(c, sr, seed, correlated)))
+        },
+    );
+
+    // Arithmetics
+    r.register(
+        &["+"],
+        Ports(function::FN2_INPUTS),
+        "x + y",
+        |c, _, _, _| Ok(Box::new(Fn2::new(c, add))),
+    );
+    r.register(
+        &["-"],
+        Ports(function::FN2_INPUTS),
+        "x - y",
+        |c, _, _, _| Ok(Box::new(Fn2::new(c, sub))),
+    );
+    r.register(
+        &["*"],
+        Ports(function::FN2_INPUTS),
+        "x * y",
+        |c, _, _, _| Ok(Box::new(Fn2::new(c, mul))),
+    );
+    r.register(
+        &["/"],
+        Ports(function::FN2_INPUTS),
+        "x / y",
+        |c, _, _, _| Ok(Box::new(Fn2::new(c, div))),
+    );
+    r.register(
+        &["\\"],
+        Ports(function::FN1_INPUTS),
+        "1 / x",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, recip))),
+    );
+    r.register(
+        &["^", "pow"],
+        Ports(function::FN2_INPUTS),
+        "x to the power of y",
+        |c, _, _, _| Ok(Box::new(Fn2::new(c, pow))),
+    );
+    r.register(
+        &["round"],
+        Ports(function::FN1_INPUTS),
+        "round to the nearest integer",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, round))),
+    );
+    r.register(
+        &["quantize"],
+        Ports(function::FN2_INPUTS),
+        "round x to the nearest multiple of step: x, step",
+        |c, _, _, _| Ok(Box::new(Fn2::new(c, quantize))),
+    );
+    r.register(
+        &["sin"],
+        Ports(function::FN1_INPUTS),
+        "sin(x)",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, sin))),
+    );
+    r.register(
+        &["cos"],
+        Ports(function::FN1_INPUTS),
+        "cos(x)",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, cos))),
+    );
+
+    // Projections and conversions
+    r.register(
+        &["unit"],
+        Ports(function::FN1_INPUTS),
+        "project -1..1 to 0..1",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, unit))),
+    );
+    r.register(
+        &["r", "range"],
+        Ports(function::FN3_INPUTS),
+        "project -1..1 to a..b: x, a, b",
+        |c, _, _, _| Ok(Box::new(Fn3::new(c, range))),
+    );
+    r.register(
+        &["m2f", "midi2freq"],
+        Ports(function::FN1_INPUTS),
+        "convert MIDI pitch to frequency",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, midi2freq))),
+    );
+
+    // Waveshapers
+    r.register(
+        &["cheb2"],
+        Ports(function::FN1_INPUTS),
+        "Chebyshev polynomial of degree 2",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, cheb2))),
+    );
+    r.register(
+        &["cheb3"],
+        Ports(function::FN1_INPUTS),
+        "Chebyshev polynomial of degree 3",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, cheb3))),
+    );
+    r.register(
+        &["cheb4"],
+        Ports(function::FN1_INPUTS),
+        "Chebyshev polynomial of degree 4",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, cheb4))),
+    );
+    r.register(
+        &["cheb5"],
+        Ports(function::FN1_INPUTS),
+        "Chebyshev polynomial of degree 5",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, cheb5))),
+    );
+    r.register(
+        &["cheb6"],
+        Ports(function::FN1_INPUTS),
+        "Chebyshev polynomial of degree 6",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, cheb6))),
+    );
+
+    // Delays and filters
+    r.register(
+        &["delay"],
+        Ports(delay::INPUTS),
+        "delay up to 10 seconds or delay:<max> up to 60: input, delay time",
+        |c, sr, _, args| Ok(Box::new(Delay::new(c, sr, parse_max_delay(args)?))),
+    );
+    r.register(
+        &["fb", "feedback"],
+        Ports(feedback::INPUTS),
+        "feedback comb filter, fb:<max> as in delay: input, delay time, gain",
+        |c, sr, _, args| Ok(Box::new(Feedback::new(c, sr, parse_max_delay(args)?))),
+    );
+    r.register(
+        &["lpf"],
+        Ports(filter::INPUTS),
+        "one-pole low-pass filter: input, cut-off frequency",
+        |c, sr, _, _| Ok(Box::new(LPF::new(c, sr))),
+    );
+    r.register(
+        &["hpf"],
+        Ports(filter::INPUTS),
+        "one-pole high-pass filter: input, cut-off frequency",
+        |c, sr, _, _| Ok(Box::new(HPF::new(c, sr))),
+    );
+    r.register(
+        &["l", "bqlpf"],
+        Ports(biquad::INPUTS),
+        "biquad low-pass filter: input, cut-off frequency, Q",
+        |c, sr, _, _| Ok(Box::new(BiQuad::new(c, sr, make_lpf_coefficients))),
+    );
+    r.register(
+        &["h", "bqhpf"],
+        Ports(biquad::INPUTS),
+        "biquad high-pass filter: input, cut-off frequency, Q",
+        |c, sr, _, _| Ok(Box::new(BiQuad::new(c, sr, make_hpf_coefficients))),
+    );
+    r.register(
+        &["bqbpf"],
+        Ports(biquad::INPUTS),
+        "biquad band-pass filter with 0 dB peak: input, center frequency, Q; bqbpf:skirt for constant skirt gain",
+        |c, sr, _, args| match args {
+            [] => Ok(Box::new(BiQuad::new(c, sr, make_bpf_peak_coefficients))),
+            ["skirt"] => Ok(Box::new(BiQuad::new(c, sr, make_bpf_skirt_coefficients))),
+            _ => Err("expected no options or skirt".to_string()),
+        },
+    );
+    r.register(
+        &["bqnotch"],
+        Ports(biquad::INPUTS),
+        "biquad notch filter: input, center frequency, Q",
+        |c, sr, _, _| Ok(Box::new(BiQuad::new(c, sr, make_notch_coefficients))),
+    );
+    r.register(
+        &["bqap"],
+        Ports(biquad::INPUTS),
+        "biquad all-pass filter: input, center frequency, Q",
+        |c, sr, _, _| Ok(Box::new(BiQuad::new(c, sr, make_allpass_coefficients))),
+    );
+    r.register(
+        &["bqpeak"],
+        Ports(biquad::GAIN_INPUTS),
+        "biquad peaking EQ: input, center frequency, Q, gain in dB",
+        |c, sr, _, _| Ok(Box::new(BiQuad::with_gain(c, sr, make_peak_coefficients))),
+    );
+    r.register(
+        &["bqlshelf"],
+        Ports(biquad::GAIN_INPUTS),
+        "biquad low shelf: input, corner frequency, Q, gain in dB",
+        |c, sr, _, _| {
+            Ok(Box::new(BiQuad::with_gain(
+                c,
+                sr,
+                make_low_shelf_coefficients,
+            )))
+        },
+    );
+    r.register(
+        &["bqhshelf"],
+        Ports(biquad::GAIN_INPUTS),
+        "biquad high shelf: input, corner frequency, Q, gain in dB",
+        |c, sr, _, _| {
+            Ok(Box::new(BiQuad::with_gain(
+                c,
+                sr,
+                make_high_shelf_coefficients,
+            )))
+        },
+    );
+    r.register(
+        &["svf"],
+        Ports(svf::INPUTS),
+        "state-variable filter: input, cut-off frequency, Q; svf:lp (default), svf:bp, svf:hp or svf:notch",
+        |c, sr, _, args| Ok(Box::new(StateVariable::new(c, sr, parse_svf_args(args)?))),
+    );
+    r.register(
+        &["ladder"],
+        Ports(ladder::INPUTS),
+        "Moog-style ladder low-pass filter: input, cut-off frequency, resonance, drive",
+        |c, sr, _, _| Ok(Box::new(Ladder::new(c, sr))),
+    );
+
+    // Stereo
+    r.register(
+        &["pan"],
+        Ports(pan::PAN2_INPUTS),
+        "pan two signals per channel: left, right, position",
+        |c, _, _, _| Ok(Box::new(Pan3::new(c))),
+    );
+    r.register(
+        &["pan1"],
+        Ports(pan::PAN1_INPUTS),
+        "pan stereo signal: input, position",
+        |c, _, _, _| Ok(Box::new(Pan1::new(c))),
+    );
+    r.register(
+        &["pan2"],
+        Ports(pan::PAN2_INPUTS),
+        "pan left of the first and right of the second signal: left, right, position",
+        |c, _, _, _| Ok(Box::new(Pan2::new(c))),
+    );
+    r.register(
+        &["zip"],
+        Channels,
+        "zip the first channel of each source into multi-channel output",
+        |c, _, _, _| Ok(Box::new(Zip::new(c))),
+    );
+
+    // Envelopes
+    r.register(
+        &["adsr"],
+        Ports(envelope::ADSR_INPUTS),
+        "ADSR envelope: gate, attack, decay, sustain, release; adsr:exp, adsr:hard",
+        |c, sr, _, args| {
+            let (curve, hard_retrigger) = parse_envelope_args(args)?;
+            Ok(Box::new(Envelope::new(
+                c,
+                sr,
+                Shape::Adsr,
+                curve,
+                hard_retrigger,
+            )))
+        },
+    );
+    r.register(
+        &["ar"],
+        Ports(envelope::AR_INPUTS),
+        "AR envelope: gate, attack, release; ar:exp, ar:hard",
+        |c, sr, _, args| {
+            let (curve, hard_retrigger) = parse_envelope_args(args)?;
+            Ok(Box::new(Envelope::new(
+                c,
+                sr,
+                Shape::Ar,
+                curve,
+                hard_retrigger,
+            )))
+        },
+    );
+    r.register(
+        &["perc"],
+        Ports(envelope::PERC_INPUTS),
+        "percussive envelope: trigger, attack, release; perc:exp, perc:hard",
+        |c, sr, _, args| {
+            let (curve, hard_retrigger) = parse_envelope_args(args)?;
+            Ok(Box::new(Envelope::new(
+                c,
+                sr,
+                Shape::Perc,
+                curve,
+                hard_retrigger,
+            )))
+        },
+    );
+
+    // Triggers
+    r.register(
+        &["sh", "sample&hold"],
+        Ports(sample_and_hold::INPUTS),
+        "sample input when trigger fires: trigger, input",
+        |c, _, _, _| Ok(Box::new(SampleAndHold::new(c))),
+    );
+    r.register(
+        &["m", "metro"],
+        Ports(metro::METRO_INPUTS),
+        "trigger at frequency",
+        |c, sr, _, _| Ok(Box::new(Metro::new(c, sr))),
+    );
+    r.register(
+        &["dm", "dmetro"],
+        Ports(metro::DMETRO_INPUTS),
+        "trigger every period in seconds",
+        |c, sr, _, _| Ok(Box::new(DMetro::new(c, sr))),
+    );
+    r.register(
+        &["mh", "metroHold"],
+        Ports(metro::METRO_INPUTS),
+        "trigger at frequency sampled on trigger",
+        |c, sr, _, _| Ok(Box::new(MetroHold::new(c, sr))),
+    );
+    r.register(
+        &["dmh", "dmetroHold"],
+        Ports(metro::DMETRO_INPUTS),
+        "trigger every period in seconds sampled on trigger",
+        |c, sr, _, _| Ok(Box::new(DMetroHold::new(c, sr))),
+    );
+    r.register(
+        &["bm", "beatMetro"],
+        Ports(metro::BEAT_METRO_INPUTS),
+        "trigger when beat crosses a multiple of division: beat, division",
+        |c, _, _, _| Ok(Box::new(BeatMetro::new(c))),
+    );
+
+    // Analysis
+    r.register(
+        &["yin", "pitch"],
+        Ports(yin::INPUTS),
+        "detect pitch of input",
+        |c, sr, _, _| Ok(Box::new(Yin::new(c, sr, 1024, 512, 0.2))),
+    );
+
+    // External input
+    r.register(
+        &["in", "input"],
+        Ports(&[]),
+        "external audio input",
+        |c, _, _, _| Ok(Box::new(Input::new(c))),
+    );
+    r.register(
+        &["param"],
+        Ports(&[]),
+        "external parameter by index, e.g. param:0",
+        |c, _, parameters, args| {
+            let index = parse_arg(args, 0, "parameter index")?;
+            if index >= parameters {
+                return Err(format!(
+                    "parameter index {} is out of range, there are {} parameters",
+                    index, parameters
+                ));
+            }
+            Ok(Box::new(Parameter::new(c, index)))
+        },
+    );
+}
@@ -0,0 +1,208 @@
+//! Golden-audio regression tests.
+//!
+//! Each case renders a short program and compares the result with the reference buffer in
+//! `tests/golden/<name>.txt` (a line per frame, channels separated by spaces). After an intended
+//! change in sound regenerate references with:
+//!
+//! ```sh
+//! UPDATE_GOLDEN=1 cargo test --test golden
+//! ```
+use audio_graph::lang::compile;
+use audio_graph::prelude::*;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const CHANNELS: usize = 2;
+const SAMPLE_RATE: usize = 48_000;
+const PARAMETERS: usize = 2;
+const FRAMES: usize = 256;
+const TOLERANCE: Sample = 1e-6;
+
+/// Name of reference file, program and number of frames to render.
+const CASES: &[(&str, &str, usize)] = &[
+    // Oscillators
+    ("sine", "440 s", FRAMES),
+    ("sine_phase", "440 1000 s sine", FRAMES),
+    ("triangle", "440 t", FRAMES),
+    ("triangle_phase", "440 0.25 tri", FRAMES),
+    ("phasor", "440 w", FRAMES),
+    ("phasor_wrap", "20000 w", FRAMES),
+    ("phasor_phase", "440 0.5 saw", FRAMES),
+    ("pulse", "1000 0.25 p", FRAMES),
+    ("blsaw", "5000 blsaw", FRAMES),
+    ("blsquare", "5000 0.3 blsquare", FRAMES),
+    ("bltri", "5000 bltri", FRAMES),
+    ("blsquare_width", "5000 200 s 1.2 * unit blsquare", FRAMES),
+    ("blsaw_sweep", "10 w 100 20000 r blsaw", FRAMES),
+    ("blsaw_fm", "200 s 8000 * blsaw", FRAMES),
+    ("blsquare_negative", "-5000 0.3 blsquare", FRAMES),
+    ("bltri_negative", "-5000 bltri", FRAMES),
+    ("noise", "noise:42", FRAMES),
+    ("noise_correlated", "noise:42:correlated", FRAMES),
+    ("pink", "pink:42", FRAMES),
+    ("brown", "brown:42", FRAMES),
+    ("velvet", "2000 velvet:42", FRAMES),
+    (
+        "velvet_correlated",
+        "1000 s 1000 * 2000 + velvet:42:correlated",
+        FRAMES,
+    ),
+    // Functions
+    ("constant", "0.5", 4),
+    ("fn1", "440 s cheb3", FRAMES),
+    ("fn2", "440 s 220 t *", FRAMES),
+    ("fn3", "440 s 100 1000 r", FRAMES),
+    // Delays and filters
+    ("delay", "1000 0.5 p 0.001 delay", FRAMES),
+    ("delay_fractional", "1000 s 0.000123 delay", FRAMES),
+    (
+        "delay_modulated",
+        "1000 s 100 s 0.0005 * 0.001 + delay",
+        FRAMES,
+    ),
+    ("feedback", "1000 m 0.0005 0.7 fb", FRAMES),
+    ("lpf", "1000 w 2000 lpf", FRAMES),
+    ("hpf", "1000 w 2000 hpf", FRAMES),
+    ("bqlpf", "1000 w 3000 2 bqlpf", FRAMES),
+    ("bqhpf", "1000 w 3000 2 bqhpf", FRAMES),
+    ("bqbpf", "1000 w 3000 2 bqbpf", FRAMES),
+    ("bqbpf_skirt", "1000 w 3000 2 bqbpf:skirt", FRAMES),
+    ("bqnotch", "1000 w 3000 2 bqnotch", FRAMES),
+    ("bqap", "1000 w 3000 2 bqap", FRAMES),
+    ("bqpeak", "1000 w 3000 2 12 bqpeak", FRAMES),
+    ("bqlshelf", "1000 w 3000 0.707 -12 bqlshelf", FRAMES),
+    ("bqhshelf", "1000 w 3000 0.707 6 bqhshelf", FRAMES),
+    ("svf", "1000 w 3000 2 svf", FRAMES),
+    ("svf_bp", "1000 w 3000 2 svf:bp", FRAMES),
+    ("svf_hp", "1000 w 3000 2 svf:hp", FRAMES),
+    ("svf_notch", "1000 w 3000 2 svf:notch", FRAMES),
+    ("svf_modulated", "1000 w 5000 s 100 10000 r 4 svf", FRAMES),
+    ("ladder", "1000 w 3000 0.5 1 ladder", FRAMES),
+    ("ladder_drive", "1000 w 3000 0.2 5 ladder", FRAMES),
+    ("ladder_self_oscillation", "0.01 2000 1.2 1 ladder", 1024),
+    (
+        "bqlpf_modulated",
+        "1000 w 50 s 1000 5000 r 0.707 bqlpf",
+        FRAMES,
+    ),
+    // Envelopes
+    ("adsr", "200 0.5 p 0.0005 0.001 0.5 0.001 adsr", 768),
+    ("adsr_exp", "200 0.5 p 0.0005 0.001 0.5 0.001 adsr:exp", 768),
+    (
+        "adsr_exp_full_sustain",
+        "1 0.001 0.001 1 0.01 adsr:exp",
+        768,
+    ),
+    ("ar", "200 0.5 p 0.001 0.0005 ar", 768),
+    ("perc", "300 m 0.0005 0.004 perc", 768),
+    ("perc_hard", "300 m 0.0005 0.004 perc:exp:hard", 768),
+    // Stereo
+    ("pan1", "440 s 0.5 pan1", FRAMES),
+    ("pan2", "440 s 220 t -0.5 pan2", FRAMES),
+    ("pan3", "440 s 220 t 100 s pan", FRAMES),
+    ("zip", "440 s 220 t zip", FRAMES),
+    // Triggers
+    ("sample_and_hold", "1000 m 440 s sh", FRAMES),
+    ("metro", "1000 m", FRAMES),
+    ("dmetro", "0.001 dm", FRAMES),
+    ("metro_hold", "1000 50 s 500 * + mh", FRAMES),
+    ("dmetro_hold", "0.001 dmh", FRAMES),
+    ("beat_metro", "100 w 0.25 bm", FRAMES),
+    // Feedback loops
+    ("unit_delay", "recv:x 0.9 * 1000 m + send:x", FRAMES),
+    // Analysis
+    ("yin", "1000 s yin", 1536),
+    // External input
+    ("input", "in", FRAMES),
+    ("parameter", "param:0 param:1 +", 4),
+];
+
+/// Deterministic external input: a sine per channel followed by parameters.
+fn input(frame: usize) -> Vec<Sample> {
+    let t = frame as Sample;
+    vec![(0.05 * t).sin(), (0.03 * t).cos(), 0.25, -0.5]
+}
+
+fn render(text: &str, frames: usize) -> Vec<Vec<Sample>> {
+    let mut g = compile(text, CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    (0..frames).map(|i| g.sample(&input(i)).to_vec()).collect()
+}
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.txt", name))
+}
+
+fn to_text(frames: &[Vec<Sample>]) -> String {
+    let mut text = String::new();
+    for frame in frames {
+        let line = frame
+            .iter()
+            .map(|x| format!("{:.9}", x))
+            .collect::<Vec<_>>()
+            .join(" ");
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
+
+fn from_text(text: &str) -> Vec<Vec<Sample>> {
+    text.lines()
+        .map(|line| {
+            line.split_whitespace()
+                .map(|x| x.parse().unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+/// Index of the first frame which differs from the reference more than TOLERANCE.
+fn first_mismatch(rendered: &[Vec<Sample>], reference: &[Vec<Sample>]) -> Option<usize> {
+    if rendered.len() != reference.len() {
+        return Some(rendered.len().min(reference.len()));
+    }
+    rendered.iter().zip(reference).position(|(x, y)| {
+        x.len() != y.len()
+            || x.iter().zip(y).any(|(x, y)| {
+                // `x == y` covers infinities, NaN matches NaN.
+                !(x == y || (x - y).abs() <= TOLERANCE || (x.is_nan() && y.is_nan()))
+            })
+    })
+}
+
+#[test]
+fn golden() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+    for &(name, text, frames) in CASES {
+        let rendered = render(text, frames);
+        let path = path(name);
+        if update {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, to_text(&rendered)).unwrap();
+            continue;
+        }
+        let reference = match fs::read_to_string(&path) {
+            Ok(text) => from_text(&text),
+            Err(e) => {
+                failures.push(format!("{}: can't read {}: {}", name, path.display(), e));
+                continue;
+            }
+        };
+        if let Some(frame) = first_mismatch(&rendered, &reference) {
+            failures.push(format!(
+                "{} `{}`: differs from the reference at frame {}",
+                name, text, frame
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\nIf the change is intended, regenerate references with UPDATE_GOLDEN=1.",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,241 @@
+use audio_graph::lang::compile;
+
+const CHANNELS: usize = 1;
+const SAMPLE_RATE: usize = 100;
+
+fn run(g: &mut audio_graph::graph::AudioGraph, frames: usize) -> f64 {
+    let mut x = 0.0;
+    for _ in 0..frames {
+        x = g.sample(&[0.0])[0];
+    }
+    x
+}
+
+#[test]
+fn transplant_keeps_state_of_unchanged_nodes() {
+    // Phasor of 1 Hz advances by 0.01 per frame.
+    let mut old = compile("1 w 2 *", CHANNELS, SAMPLE_RATE, 0).unwrap();
+    let x = run(&mut old, 10);
+    assert!((x - 0.2).abs() < 1e-9);
+
+    // Constants are tweaked, but phasor is at the same place and continues from its phase.
+    let mut new = compile("2 w 3 *", CHANNELS, SAMPLE_RATE, 0).unwrap();
+    assert!(new.transplant(&mut old));
+    let x = run(&mut new, 1);
+    assert!((x - 0.36).abs() < 1e-9);
+
+    // Different module at the same place doesn't take the state.
+    let mut new = compile("1 t 2 *", CHANNELS, SAMPLE_RATE, 0).unwrap();
+    assert!(!new.transplant(&mut old));
+    let x = run(&mut new, 1);
+    assert!((x - 1.96).abs() < 1e-9);
+}
+
+#[test]
+fn transplant_keeps_delay_buffer() {
+    // Delay by 5 frames.
+    let mut old = compile("in 0.05 delay", CHANNELS, SAMPLE_RATE, 0).unwrap();
+    run(&mut old, 1);
+    old.sample(&[1.0]);
+    run(&mut old, 1);
+    let mut new = compile("in 0.05 delay", CHANNELS, SAMPLE_RATE, 0).unwrap();
+    assert!(new.transplant(&mut old));
+    assert_eq!(run(&mut new, 3), 0.0);
+    assert_eq!(run(&mut new, 1), 1.0);
+    // Buffer is shared, the old graph keeps playing the same tail while it fades out.
+    assert_eq!(run(&mut old, 3), 0.0);
+    assert_eq!(run(&mut old, 1), 1.0);
+    // Only the new graph writes into the buffer.
+    old.sample(&[2.0]);
+    new.sample(&[3.0]);
+    assert_eq!(run(&mut old, 4), 0.0);
+    assert_eq!(old.sample(&[0.0])[0], 3.0);
+    // Graphs with extra nodes still transplant, but are not the same.
+    let mut new = compile("in 0.05 delay 0.5 *", CHANNELS, SAMPLE_RATE, 0).unwrap();
+    assert!(!new.transplant(&mut old));
+}
+
+#[test]
+fn cycles_are_reported() {
+    use audio_graph::graph::{AudioGraph, GraphError};
+    use audio_graph::prelude::*;
+    let mut g = AudioGraph::new(CHANNELS, 0);
+    let a = g.add_node(Box::new(Fn1::new(CHANNELS, recip)));
+    let b = g.add_node(Box::new(Fn1::new(CHANNELS, recip)));
+    let c = g.add_node(Box::new(Constant::new(CHANNELS, 1.0)));
+    assert!(g.connect(c, a).is_ok());
+    assert!(g.connect(a, b).is_ok());
+    match g.connect(b, a) {
+        Err(GraphError::Cycle(mut nodes)) => {
+            nodes.sort();
+            assert_eq!(nodes, vec![a, b]);
+        }
+        _ => panic!("cycle is not detected"),
+    }
+    // Breaking the cycle brings the graph back.
+    assert!(g.connect(c, a).is_ok());
+    assert_eq!(run(&mut g, 1), 1.0);
+}
+
+#[test]
+fn process_block_matches_sample() {
+    const CHANNELS: usize = 2;
+    const FRAMES: usize = 300;
+    let programs = [
+        "3 w 0.5 * 5 s +",
+        "in param:0 * 0.02 delay 7 t 0.3 2 l",
+        "recv:x 0.5 * in + send:x",
+        "",
+    ];
+    // Planar input: a ramp per channel and a constant parameter.
+    let left = (0..FRAMES).map(|i| i as f64 / 10.0).collect::<Vec<_>>();
+    let right = left.iter().map(|x| -x).collect::<Vec<_>>();
+    let parameter = vec![0.5; FRAMES];
+    let input: [&[f64]; 3] = [&left, &right, &parameter];
+    for program in &programs {
+        let mut expected = compile(program, CHANNELS, SAMPLE_RATE, 1).unwrap();
+        let mut actual = compile(program, CHANNELS, SAMPLE_RATE, 1).unwrap();
+        let mut left_out = vec![0.0; FRAMES];
+        let mut right_out = vec![0.0; FRAMES];
+        // Uneven host buffers which don't match internal block size.
+        let mut offset = 0;
+        for &frames in &[1, 100, 64, 135] {
+            let input = input
+                .iter()
+                .map(|x| &x[offset..offset + frames])
+                .collect::<Vec<_>>();
+            let mut output = [
+                &mut left_out[offset..offset + frames],
+                &mut right_out[offset..offset + frames],
+            ];
+            actual.process_block(&input, &mut output, frames);
+            offset += frames;
+        }
+        for i in 0..FRAMES {
+            let frame = expected.sample(&[left[i], right[i], parameter[i]]);
+            if program.is_empty() {
+                // Empty graph output is unspecified for `sample`.
+                assert_eq!([left_out[i], right_out[i]], [0.0, 0.0]);
+            } else {
+                assert_eq!(
+                    [left_out[i], right_out[i]],
+                    frame[..2],
+                    "{} at {}",
+                    program,
+                    i
+                );
+            }
+        }
+    }
+}
+
+/// Sum of all sources.
+struct Mix {
+    inputs: usize,
+    output: Vec<f64>,
+}
+
+impl audio_graph::module::Module for Mix {
+    fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    fn output(&self) -> &[f64] {
+        &self.output
+    }
+
+    fn sample(&mut self, input: &[f64]) {
+        let channels = self.output.len();
+        for (channel, output) in self.output.iter_mut().enumerate() {
+            *output = input[..self.inputs * channels]
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .sum();
+        }
+    }
+}
+
+#[test]
+fn many_sources() {
+    use audio_graph::prelude::*;
+    const CHANNELS: usize = 2;
+    const INPUTS: usize = 64;
+    let mut g = AudioGraph::new(CHANNELS, 0);
+    let sources = (0..INPUTS)
+        .map(|i| g.add_node(Box::new(Constant::new(CHANNELS, i as f64))))
+        .collect::<Vec<_>>();
+    let mix = g.add_node(Box::new(Mix {
+        inputs: INPUTS,
+        output: vec![0.0; CHANNELS],
+    }));
+    assert!(g.set_sources(mix, &sources).is_ok());
+    let sum = (INPUTS * (INPUTS - 1) / 2) as f64;
+    assert_eq!(g.sample(&[0.0; CHANNELS]), &[sum, sum]);
+
+    let mut left = [0.0; 100];
+    let mut right = [0.0; 100];
+    g.process_block(
+        &[&[0.0; 100], &[0.0; 100]],
+        &mut [&mut left, &mut right],
+        100,
+    );
+    assert!(left.iter().chain(right.iter()).all(|x| *x == sum));
+}
+
+#[test]
+fn extra_sources_are_rejected() {
+    use audio_graph::graph::{AudioGraph, GraphError};
+    use audio_graph::prelude::*;
+    const CHANNELS: usize = 2;
+    let mut g = AudioGraph::new(CHANNELS, 0);
+    let sources = (0..3)
+        .map(|i| g.add_node(Box::new(Constant::new(CHANNELS, i as f64))))
+        .collect::<Vec<_>>();
+    let mix = g.add_node(Box::new(Mix {
+        inputs: 2,
+        output: vec![0.0; CHANNELS],
+    }));
+    assert!(g.set_sources(mix, &sources[..2]).is_ok());
+    assert_eq!(
+        g.set_sources(mix, &sources),
+        Err(GraphError::TooManySources(mix, 2))
+    );
+    assert_eq!(
+        g.set_sources_rev(mix, &sources),
+        Err(GraphError::TooManySources(mix, 2))
+    );
+    // Sources without inputs don't take any.
+    assert_eq!(
+        g.connect(sources[0], sources[1]),
+        Err(GraphError::TooManySources(sources[1], 0))
+    );
+    // Rejected connections leave the graph as it was.
+    assert_eq!(g.sample(&[0.0; CHANNELS]), &[1.0, 1.0]);
+    let mut left = [0.0; 10];
+    let mut right = [0.0; 10];
+    g.process_block(&[&[0.0; 10], &[0.0; 10]], &mut [&mut left, &mut right], 10);
+    assert!(left.iter().chain(right.iter()).all(|x| *x == 1.0));
+}
+
+#[test]
+fn connect_by_port_name() {
+    use audio_graph::graph::{AudioGraph, GraphError};
+    use audio_graph::prelude::*;
+    let mut g = AudioGraph::new(CHANNELS, 0);
+    let a = g.add_node(Box::new(Constant::new(CHANNELS, 3.0)));
+    let b = g.add_node(Box::new(Constant::new(CHANNELS, 1.0)));
+    let c = g.add_node(Box::new(Constant::new(CHANNELS, 2.0)));
+    let sub = g.add_node(Box::new(Fn2::new(CHANNELS, sub)));
+    // Ports are filled regardless of the order of connection.
+    assert!(g.connect_port(b, sub, "y").is_ok());
+    assert!(g.connect_port(a, sub, "x").is_ok());
+    assert_eq!(run(&mut g, 1), 2.0);
+    // Connecting to the same port replaces the source.
+    assert!(g.connect_port(c, sub, "y").is_ok());
+    assert_eq!(run(&mut g, 1), 1.0);
+    assert_eq!(
+        g.connect_port(a, sub, "z"),
+        Err(GraphError::UnknownPort(sub, "z".to_string()))
+    );
+}
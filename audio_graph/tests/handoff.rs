@@ -0,0 +1,52 @@
+use audio_graph::handoff;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts destructions to check on which side values are dropped.
+struct Tracked(Arc<AtomicUsize>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn retired_values_are_destroyed_by_sender() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = handoff::channel();
+    assert!(receiver.receive().is_none());
+
+    sender.send(Box::new(Tracked(drops.clone())));
+    let mut current = receiver.receive().unwrap();
+    assert!(receiver.receive().is_none());
+
+    sender.send(Box::new(Tracked(drops.clone())));
+    let old = std::mem::replace(&mut current, receiver.receive().unwrap());
+    assert!(receiver.retire(old).is_ok());
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+    // The retired slot is occupied until sender collects it.
+    let rejected = receiver.retire(current).err().unwrap();
+    sender.collect();
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+    assert!(receiver.retire(rejected).is_ok());
+
+    // Sender collects retired values before sending new ones.
+    sender.send(Box::new(Tracked(drops.clone())));
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+    assert!(receiver.receive().is_some());
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn unreceived_value_is_replaced() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = handoff::channel();
+    sender.send(Box::new(Tracked(drops.clone())));
+    sender.send(Box::new(Tracked(drops.clone())));
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+    drop(sender);
+    drop(receiver);
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,249 @@
+use audio_graph::lang::{compile, compile_with, ErrorKind};
+use audio_graph::prelude::*;
+
+const CHANNELS: usize = 2;
+const SAMPLE_RATE: usize = 48_000;
+const PARAMETERS: usize = 4;
+
+#[test]
+fn arithmetic() {
+    let mut g = compile("2 3 + 4 *", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    let input = [0.0; CHANNELS + PARAMETERS];
+    assert_eq!(g.sample(&input), &[20.0, 20.0]);
+}
+
+#[test]
+fn stack_operations() {
+    let input = [0.0; CHANNELS + PARAMETERS];
+    let mut g = compile("1 2 swap -", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    assert_eq!(g.sample(&input), &[1.0, 1.0]);
+    let mut g = compile("3 dup *", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    assert_eq!(g.sample(&input), &[9.0, 9.0]);
+    let mut g = compile("1 2 3 rot - -", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    assert_eq!(g.sample(&input), &[0.0, 0.0]);
+}
+
+#[test]
+fn parameters_and_input() {
+    let mut g = compile("in param:2 +", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    let input = [1.0, -1.0, 0.0, 0.0, 0.5, 0.0];
+    assert_eq!(g.sample(&input), &[1.5, -0.5]);
+
+    let e = compile("param:4", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.to_string(),
+        "Node #1 `param:4`: parameter index 4 is out of range, there are 4 parameters."
+    );
+}
+
+#[test]
+fn errors_point_to_token() {
+    let e = compile("440 s  foo", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::UnknownModule);
+    assert_eq!(e.index, 2);
+    assert_eq!(e.span, 7..10);
+    assert_eq!(e.to_string(), "Node #3 `foo` is unknown module.");
+
+    let e = compile("1 +", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::MissingInput("y"));
+    assert_eq!(e.span, 2..3);
+
+    let e = compile("440 s bqlpf", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.to_string(), "Node #3 `bqlpf`: missing input `freq`.");
+
+    let e = compile("1 zip", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::NotEnoughInputs);
+
+    let e = compile("pop", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::NothingToPop);
+}
+
+#[test]
+fn custom_module() {
+    use audio_graph::modules::function::FN1_INPUTS;
+    use audio_graph::registry::{Inputs, ModuleRegistry};
+    let mut registry = ModuleRegistry::default();
+    registry.register(
+        &["twice"],
+        Inputs::Ports(FN1_INPUTS),
+        "x * 2",
+        |c, _, _, _| Ok(Box::new(Fn1::new(c, |x| 2.0 * x))),
+    );
+    let mut g = compile_with(&registry, "21 twice", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    let input = [0.0; CHANNELS + PARAMETERS];
+    assert_eq!(g.sample(&input), &[42.0, 42.0]);
+    assert!(registry.help().contains("twice (1): x * 2"));
+
+    let e = compile_with(&registry, "param:x", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(
+        e.to_string(),
+        "Node #1 `param:x`: invalid parameter index `x`."
+    );
+}
+
+#[test]
+fn maximum_delay() {
+    // Delay of 0.05 s at 100 Hz is 5 frames, it doesn't fit into the buffer allocated for 0.02 s
+    // and is clamped to 2 frames.
+    let mut g = compile("in 0.05 delay:0.02", 1, 100, 0).unwrap();
+    let output = (0..6).map(|i| g.sample(&[i as f64])[0]).collect::<Vec<_>>();
+    assert_eq!(output, vec![0.0, 0.0, 0.0, 1.0, 2.0, 3.0]);
+
+    for token in &["delay:0", "delay:61", "fb:x", "delay:1:2"] {
+        let program = format!("in 1 {}", token);
+        assert!(compile(&program, 1, 100, 0).is_err(), "{}", token);
+    }
+}
+
+#[test]
+fn unit_delay_feedback() {
+    let input = [0.0; CHANNELS + PARAMETERS];
+    // Counter: every frame adds one to the previous one.
+    let mut g = compile("recv:x 1 + send:x", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    assert_eq!(g.sample(&input), &[1.0, 1.0]);
+    assert_eq!(g.sample(&input), &[2.0, 2.0]);
+    assert_eq!(g.sample(&input), &[3.0, 3.0]);
+    // Receive is delayed by one frame even if it is sampled after send.
+    let mut g = compile("1 send:x recv:x +", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    assert_eq!(g.sample(&input), &[1.0, 1.0]);
+    assert_eq!(g.sample(&input), &[2.0, 2.0]);
+
+    let e = compile("recv:y 1 + send:x", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::NoSend);
+    assert_eq!(e.index, 0);
+    let e = compile("1 send:x send:x", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::DuplicateSend);
+    assert_eq!(e.index, 2);
+}
+
+#[test]
+fn signatures() {
+    use audio_graph::registry::ModuleRegistry;
+    let registry = ModuleRegistry::default();
+    assert_eq!(
+        registry.signature("bqlpf").unwrap(),
+        "bqlpf ( input freq q=0.707 -- out )"
+    );
+    assert_eq!(registry.signature("param:1").unwrap(), "param:1 ( -- out )");
+    assert_eq!(registry.signature("param").unwrap(), "param ( -- out )");
+    assert_eq!(
+        registry.signature("zip").unwrap(),
+        "zip ( channels inputs -- out )"
+    );
+    assert!(registry.signature("foo").is_none());
+}
+
+#[test]
+fn registry_inputs_match_modules() {
+    use audio_graph::registry::{Inputs, ModuleRegistry};
+    let registry = ModuleRegistry::default();
+    for entry in registry.entries() {
+        // Modules which require arguments are skipped.
+        let node = match registry.construct(entry.names[0], CHANNELS, SAMPLE_RATE, PARAMETERS) {
+            Some(Ok(node)) => node,
+            _ => continue,
+        };
+        match entry.inputs {
+            Inputs::Ports(ports) => {
+                assert_eq!(node.input_ports(), ports, "{}", entry.names[0]);
+                assert_eq!(node.inputs(), ports.len(), "{}", entry.names[0]);
+            }
+            Inputs::Channels => assert_eq!(node.inputs(), CHANNELS, "{}", entry.names[0]),
+        }
+    }
+}
+
+#[test]
+fn default_inputs() {
+    let input = [0.0; CHANNELS + PARAMETERS];
+    let mut partial = compile("440 s 1000 bqlpf", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    let mut full = compile("440 s 1000 0.707 bqlpf", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    for _ in 0..100 {
+        assert_eq!(partial.sample(&input), full.sample(&input));
+    }
+    let e = compile("1 fb", CHANNELS, SAMPLE_RATE, PARAMETERS)
+        .err()
+        .unwrap();
+    assert_eq!(e.kind, ErrorKind::MissingInput("time"));
+}
+
+#[test]
+fn beat_metro() {
+    let mut g = compile("param:0 0.25 bm", CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+    let beats = [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.5, 0.5, 0.0, 0.6];
+    let triggers = beats
+        .iter()
+        .map(|&beat| g.sample(&[0.0, 0.0, beat, 0.0, 0.0, 0.0])[0])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        triggers,
+        &[1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0]
+    );
+}
+
+#[test]
+fn seeded_noise() {
+    let input = [0.0; CHANNELS + PARAMETERS];
+    let render = |text| {
+        let mut g = compile(text, CHANNELS, SAMPLE_RATE, PARAMETERS).unwrap();
+        (0..16)
+            .map(|_| g.sample(&input).to_vec())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(render("noise:42"), render("noise:42"));
+    assert_ne!(render("noise:42"), render("noise:43"));
+    assert_ne!(render("noise"), render("noise"));
+    let independent = render("noise:42");
+    assert!(independent.iter().any(|frame| frame[0] != frame[1]));
+    let correlated = render("noise:42:correlated");
+    assert!(correlated.iter().all(|frame| frame[0] == frame[1]));
+    assert!(correlated.iter().flatten().all(|x| (-1.0..1.0).contains(x)));
+}
+
+#[test]
+fn velvet_density() {
+    // A second of velvet noise has one impulse per period.
+    for density in &[100, 2000, 3000, 7919, 24000] {
+        let program = format!("{} velvet:{}", density, density);
+        let mut g = compile(&program, 1, 48_000, 0).unwrap();
+        let impulses = (0..48_000).filter(|_| g.sample(&[0.0])[0] != 0.0).count();
+        assert_eq!(impulses, *density, "{}", density);
+    }
+}
+
+#[test]
+fn band_limited_negative_frequency() {
+    // Running phase backwards mirrors the waveform, corrections included.
+    let cases = [
+        ("1234.5 blsaw", "-1234.5 blsaw", -1.0),
+        ("1234.5 0.3 blsquare", "-1234.5 0.7 blsquare", -1.0),
+        ("1234.5 bltri", "-1234.5 bltri", 1.0),
+    ];
+    for (forward, backward, sign) in &cases {
+        let mut forward_graph = compile(forward, 1, 48_000, 0).unwrap();
+        let mut backward_graph = compile(backward, 1, 48_000, 0).unwrap();
+        for _ in 0..4800 {
+            let x = forward_graph.sample(&[0.0])[0];
+            let y = backward_graph.sample(&[0.0])[0];
+            assert!((sign * x - y).abs() < 1e-9, "{}", backward);
+        }
+    }
+}
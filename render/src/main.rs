@@ -0,0 +1,157 @@
+//! # sound-garden-render
+//!
+//! Offline renderer: compiles a program with the same compiler as the plugin and writes its
+//! output to a 32-bit float WAV file. External audio input is silence.
+//!
+//! Plugin-only sources (per-voice, MIDI controllers and transport) are not available here.
+use audio_graph::graph::BLOCK;
+use audio_graph::lang;
+use audio_graph::prelude::*;
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "\
+Usage: sound-garden-render [options] <program file>
+       sound-garden-render [options] -e <program>
+
+Options:
+  -e, --eval <program>      program text instead of file
+  -o, --output <path>       output WAV file [default: out.wav]
+  -r, --rate <Hz>           sample rate [default: 48000]
+  -c, --channels <n>        number of channels [default: 2]
+  -d, --duration <s>        duration in seconds [default: 1]
+  -p, --param <index=value> value of param:<index>, may be repeated
+  -h, --help                print this message";
+
+/// Number of `param:<N>` slots, the same as the plugin has.
+const PARAMETERS: usize = 16;
+
+struct Options {
+    text: String,
+    output: String,
+    sample_rate: usize,
+    channels: usize,
+    duration: Sample,
+    parameters: Vec<Sample>,
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let result = parse_options(&args).and_then(|options| render(&options));
+    if let Err(e) = result {
+        eprintln!("sound-garden-render: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        text: String::new(),
+        output: "out.wav".to_string(),
+        sample_rate: 48_000,
+        channels: 2,
+        duration: 1.0,
+        parameters: vec![0.0; PARAMETERS],
+    };
+    let mut text = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value of `{}`\n\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "-e" | "--eval" => text = Some(value()?.clone()),
+            "-o" | "--output" => options.output = value()?.clone(),
+            "-r" | "--rate" => options.sample_rate = parse(arg, value()?)?,
+            "-c" | "--channels" => options.channels = parse(arg, value()?)?,
+            "-d" | "--duration" => options.duration = parse(arg, value()?)?,
+            "-p" | "--param" => {
+                let value = value()?;
+                let mut parts = value.splitn(2, '=');
+                let index = parse::<usize>(arg, parts.next().unwrap())?;
+                let x = parse(arg, parts.next().unwrap_or(""))?;
+                match options.parameters.get_mut(index) {
+                    Some(parameter) => *parameter = x,
+                    None => return Err(format!("parameter index must be below {}", PARAMETERS)),
+                }
+            }
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option `{}`\n\n{}", arg, USAGE));
+            }
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument `{}`\n\n{}", arg, USAGE)),
+        }
+    }
+    options.text = match (text, path) {
+        (Some(text), None) => text,
+        (None, Some(path)) => {
+            fs::read_to_string(&path).map_err(|e| format!("can't read `{}`: {}", path, e))?
+        }
+        _ => return Err(format!("expected either program file or -e\n\n{}", USAGE)),
+    };
+    if options.channels == 0 || options.sample_rate == 0 {
+        return Err("channels and sample rate must be positive".to_string());
+    }
+    // WAV header stores them as u16 and u32.
+    if options.channels > u16::MAX as usize {
+        return Err(format!("channels must be at most {}", u16::MAX));
+    }
+    if options.sample_rate > u32::MAX as usize {
+        return Err(format!("sample rate must be at most {}", u32::MAX));
+    }
+    Ok(options)
+}
+
+fn parse<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` of `{}`", value, option))
+}
+
+fn render(options: &Options) -> Result<(), String> {
+    let channels = options.channels;
+    let mut graph = lang::compile(&options.text, channels, options.sample_rate, PARAMETERS)
+        .map_err(|e| e.to_string())?;
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate: options.sample_rate as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(&options.output, spec)
+        .map_err(|e| format!("can't create `{}`: {}", options.output, e))?;
+
+    // Silent audio input followed by constant parameters.
+    let mut input = vec![vec![0.0; BLOCK]; channels];
+    input.extend(options.parameters.iter().map(|x| vec![*x; BLOCK]));
+    let input = input.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+    let mut output = vec![vec![0.0; BLOCK]; channels];
+
+    let mut frames = (options.duration * options.sample_rate as Sample).round() as usize;
+    while frames > 0 {
+        let block = frames.min(BLOCK);
+        {
+            let mut output = output
+                .iter_mut()
+                .map(|x| x.as_mut_slice())
+                .collect::<Vec<_>>();
+            graph.process_block(&input, &mut output, block);
+        }
+        for i in 0..block {
+            for channel in output.iter() {
+                writer
+                    .write_sample(channel[i] as f32)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        frames -= block;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
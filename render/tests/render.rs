@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+fn render(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sound-garden-render"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Path in the temporary directory unique to the test.
+fn temp_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("sound-garden-render-{}-{}", process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn renders_program() {
+    let path = temp_path("program.wav");
+    let output = render(&[
+        "-e",
+        "param:1 0.5 *",
+        "-p",
+        "1=0.25",
+        "-r",
+        "1000",
+        "-c",
+        "3",
+        "-d",
+        "0.1",
+        "-o",
+        path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let mut reader = hound::WavReader::open(&path).unwrap();
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 3);
+    assert_eq!(spec.sample_rate, 1000);
+    assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+    let samples = reader
+        .samples::<f32>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(samples, vec![0.125; 300]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rejects_too_many_channels() {
+    let path = temp_path("channels.wav");
+    let output = render(&["-e", "0", "-c", "70000", "-o", path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("channels must be at most 65535"));
+    assert!(!path.exists());
+}
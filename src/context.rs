@@ -1,5 +1,43 @@
+use crate::patch::Patch;
+use crate::voices::{self, VOICES};
+use audio_graph::lang::{self, CompileError};
+use audio_graph::registry::ModuleRegistry;
+
 pub struct Context {
     pub channels: usize,
     pub sample_rate: usize,
     pub parameters: usize,
+    pub registry: ModuleRegistry,
+    /// Program shown in the editor. It is the program of the patch unless `error` is set: editor
+    /// updates it only when the program compiles, but loaded state keeps its program either way,
+    /// so it could be fixed in the editor.
+    pub text: String,
+    /// Why `text` failed to compile, editor reports it when opened.
+    pub error: Option<String>,
+    /// `text` was replaced outside of the editor, e.g. by loading state, and the open editor has
+    /// to show it instead of what it has.
+    pub reload: bool,
+}
+
+impl Context {
+    /// Compile program `text` with the current settings, once per voice if it is polyphonic.
+    pub fn compile(&self, text: &str) -> Result<Patch, CompileError> {
+        let voices = if voices::is_polyphonic(text) {
+            VOICES
+        } else {
+            1
+        };
+        let graphs = (0..voices)
+            .map(|_| {
+                lang::compile_with(
+                    &self.registry,
+                    text,
+                    self.channels,
+                    self.sample_rate,
+                    self.parameters,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Patch::new(graphs))
+    }
 }
@@ -0,0 +1,352 @@
+//! MIDI controllers: CC, pitch bend and channel aftertouch.
+//!
+//! Latest values are kept in a table which is updated by the audio thread when it applies MIDI
+//! events and read by `Controller` sources. Unlike per-voice sources they are shared by all voices
+//! and do not take slots in graph inputs.
+//!
+//! Tokens:
+//! * `cc:<N>`, `cc:<channel>:<N>`, `cc:<channel>:<N>:<smoothing>` — CC number N, 0..1;
+//! * `bend`, `bend:<channel>`, `bend:<channel>:<smoothing>` — pitch bend, -1..1;
+//! * `aftertouch`, `aftertouch:<channel>`, `aftertouch:<channel>:<smoothing>` — channel
+//!   pressure, 0..1.
+//!
+//! Channel is 1..16, or `*` to follow the latest message of any channel, which is also the default.
+//! Smoothing is the time constant in seconds of a one-pole lowpass applied to the value.
+use audio_graph::prelude::*;
+use audio_graph::registry::{Inputs, ModuleRegistry};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const MIDI_CHANNELS: usize = 16;
+/// Row of the table which stores the latest values of any channel.
+const ANY_CHANNEL: usize = MIDI_CHANNELS;
+/// Per channel: 128 CCs followed by pitch bend and aftertouch.
+const SLOTS: usize = 130;
+const BEND: usize = 128;
+const AFTERTOUCH: usize = 129;
+
+/// Table of the latest controller values.
+pub struct Controllers {
+    /// Bits of values, `SLOTS` per channel and the same for any channel at the end.
+    values: Vec<AtomicU64>,
+}
+
+impl Default for Controllers {
+    fn default() -> Self {
+        Controllers {
+            values: (0..(MIDI_CHANNELS + 1) * SLOTS)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+}
+
+impl Controllers {
+    /// Handle MIDI message, ignoring everything except CC, pitch bend and channel pressure.
+    pub fn midi(&self, data: [u8; 3]) {
+        let (slot, value) = match data[0] & 0xF0 {
+            0xB0 if data[1] < 128 => (usize::from(data[1]), Sample::from(data[2]) / 127.0),
+            0xD0 => (AFTERTOUCH, Sample::from(data[1]) / 127.0),
+            0xE0 => {
+                let bend = u16::from(data[2]) << 7 | u16::from(data[1]);
+                (BEND, (Sample::from(bend) / 8192.0 - 1.0).max(-1.0))
+            }
+            _ => return,
+        };
+        let channel = usize::from(data[0] & 0x0F);
+        for row in &[channel, ANY_CHANNEL] {
+            self.values[row * SLOTS + slot].store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    fn get(&self, index: usize) -> Sample {
+        Sample::from_bits(self.values[index].load(Ordering::Relaxed))
+    }
+}
+
+/// Source which outputs a value from the table, optionally smoothed.
+pub struct Controller {
+    controllers: Arc<Controllers>,
+    index: usize,
+    /// Coefficient of one-pole lowpass, 1 means no smoothing.
+    coefficient: Sample,
+    output: Vec<Sample>,
+}
+
+impl Controller {
+    fn new(
+        channels: usize,
+        sample_rate: usize,
+        controllers: Arc<Controllers>,
+        index: usize,
+        smoothing: Sample,
+    ) -> Self {
+        let coefficient = if smoothing > 0.0 {
+            1.0 - (-1.0 / (smoothing * sample_rate as Sample)).exp()
+        } else {
+            1.0
+        };
+        // Start from the current value rather than ramp up from zero.
+        let output = vec![controllers.get(index); channels];
+        Controller {
+            controllers,
+            index,
+            coefficient,
+            output,
+        }
+    }
+}
+
+impl Module for Controller {
+    fn output(&self) -> &Frame {
+        &self.output
+    }
+
+    fn sample(&mut self, _input: &Frame) {
+        let x = self.controllers.get(self.index);
+        for y in self.output.iter_mut() {
+            *y += self.coefficient * (x - *y);
+        }
+    }
+
+    fn transplant(&mut self, previous: &mut dyn Module) {
+        if let Some(previous) = previous.as_any().downcast_ref::<Self>() {
+            self.output.clone_from_slice(&previous.output);
+        }
+    }
+}
+
+/// Register controller sources reading from `controllers`.
+pub fn register(r: &mut ModuleRegistry, controllers: &Arc<Controllers>) {
+    let table = controllers.clone();
+    r.register(
+        &["cc"],
+        Inputs::Ports(&[]),
+        "latest MIDI CC value, 0..1: cc:<N> or cc:<channel>:<N>[:<smoothing>]",
+        move |c, sr, _, args| {
+            let (channel, number, smoothing) = match *args {
+                [number] => ("*", number, None),
+                [channel, number] => (channel, number, None),
+                [channel, number, smoothing] => (channel, number, Some(smoothing)),
+                _ => return Err("cc takes CC number, optionally channel and smoothing".into()),
+            };
+            let number = match number.parse::<usize>() {
+                Ok(number) if number < 128 => number,
+                _ => return Err(format!("invalid CC number `{}`", number)),
+            };
+            let index = parse_channel(channel)? * SLOTS + number;
+            let smoothing = parse_smoothing(smoothing)?;
+            Ok(Box::new(Controller::new(
+                c,
+                sr,
+                table.clone(),
+                index,
+                smoothing,
+            )))
+        },
+    );
+    let table = controllers.clone();
+    r.register(
+        &["bend"],
+        Inputs::Ports(&[]),
+        "MIDI pitch bend, -1..1: bend[:<channel>[:<smoothing>]]",
+        move |c, sr, _, args| {
+            let (index, smoothing) = parse_channel_args(args, BEND)?;
+            Ok(Box::new(Controller::new(
+                c,
+                sr,
+                table.clone(),
+                index,
+                smoothing,
+            )))
+        },
+    );
+    let table = controllers.clone();
+    r.register(
+        &["aftertouch"],
+        Inputs::Ports(&[]),
+        "MIDI channel pressure, 0..1: aftertouch[:<channel>[:<smoothing>]]",
+        move |c, sr, _, args| {
+            let (index, smoothing) = parse_channel_args(args, AFTERTOUCH)?;
+            Ok(Box::new(Controller::new(
+                c,
+                sr,
+                table.clone(),
+                index,
+                smoothing,
+            )))
+        },
+    );
+}
+
+/// Table index and smoothing of `bend`-like tokens, which take optional channel and smoothing.
+fn parse_channel_args(args: &[&str], slot: usize) -> Result<(usize, Sample), String> {
+    let (channel, smoothing) = match *args {
+        [] => ("*", None),
+        [channel] => (channel, None),
+        [channel, smoothing] => (channel, Some(smoothing)),
+        _ => return Err("expected optional channel and smoothing".into()),
+    };
+    Ok((
+        parse_channel(channel)? * SLOTS + slot,
+        parse_smoothing(smoothing)?,
+    ))
+}
+
+/// Row of the table, channels are numbered from 1 as in sequencers.
+fn parse_channel(channel: &str) -> Result<usize, String> {
+    match channel {
+        "*" => Ok(ANY_CHANNEL),
+        _ => match channel.parse::<usize>() {
+            Ok(n) if (1..=MIDI_CHANNELS).contains(&n) => Ok(n - 1),
+            _ => Err(format!(
+                "invalid MIDI channel `{}`, expected 1..16 or *",
+                channel
+            )),
+        },
+    }
+}
+
+fn parse_smoothing(smoothing: Option<&str>) -> Result<Sample, String> {
+    match smoothing {
+        None => Ok(0.0),
+        Some(s) => match s.parse::<Sample>() {
+            Ok(x) if x >= 0.0 => Ok(x),
+            _ => Err(format!("invalid smoothing `{}`", s)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (Arc<Controllers>, ModuleRegistry) {
+        let controllers = Arc::new(Controllers::default());
+        let mut registry = ModuleRegistry::new();
+        register(&mut registry, &controllers);
+        (controllers, registry)
+    }
+
+    fn value(registry: &ModuleRegistry, token: &str) -> Sample {
+        let mut node = registry.construct(token, 1, 48_000, 0).unwrap().unwrap();
+        node.sample(&[]);
+        node.output()[0]
+    }
+
+    fn error(registry: &ModuleRegistry, token: &str) -> String {
+        match registry.construct(token, 1, 48_000, 0).unwrap() {
+            Ok(_) => panic!("`{}` is accepted", token),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let (_, registry) = setup();
+        for token in &[
+            "cc:7",
+            "cc:*:7",
+            "cc:16:127",
+            "cc:1:7:0.01",
+            "bend",
+            "bend:*",
+            "bend:3:0.5",
+            "aftertouch",
+            "aftertouch:16",
+        ] {
+            assert!(
+                registry.construct(token, 1, 48_000, 0).unwrap().is_ok(),
+                "{}",
+                token
+            );
+        }
+        assert_eq!(
+            error(&registry, "cc"),
+            "cc takes CC number, optionally channel and smoothing"
+        );
+        assert_eq!(error(&registry, "cc:128"), "invalid CC number `128`");
+        assert_eq!(error(&registry, "cc:1:x"), "invalid CC number `x`");
+        assert_eq!(
+            error(&registry, "cc:0:7"),
+            "invalid MIDI channel `0`, expected 1..16 or *"
+        );
+        assert_eq!(
+            error(&registry, "cc:17:7"),
+            "invalid MIDI channel `17`, expected 1..16 or *"
+        );
+        assert_eq!(error(&registry, "cc:1:7:-1"), "invalid smoothing `-1`");
+        assert_eq!(error(&registry, "bend:1:x"), "invalid smoothing `x`");
+        assert_eq!(
+            error(&registry, "aftertouch:1:0:0"),
+            "expected optional channel and smoothing"
+        );
+    }
+
+    #[test]
+    fn values_are_scaled() {
+        let (controllers, registry) = setup();
+        controllers.midi([0xB0, 7, 127]);
+        assert_eq!(value(&registry, "cc:7"), 1.0);
+        controllers.midi([0xD0, 0, 0]);
+        assert_eq!(value(&registry, "aftertouch"), 0.0);
+
+        // 14-bit pitch bend, LSB first, 0x2000 is the center.
+        for &(lsb, msb, expected) in &[
+            (0x00, 0x40, 0.0),
+            (0x00, 0x00, -1.0),
+            (0x00, 0x20, -0.5),
+            (0x7F, 0x7F, 8191.0 / 8192.0),
+        ] {
+            controllers.midi([0xE0, lsb, msb]);
+            assert_eq!(value(&registry, "bend"), expected);
+        }
+    }
+
+    #[test]
+    fn channels_and_any_channel() {
+        let (controllers, registry) = setup();
+        // Channel 2 in sequencer numbering.
+        controllers.midi([0xB1, 7, 127]);
+        assert_eq!(value(&registry, "cc:2:7"), 1.0);
+        assert_eq!(value(&registry, "cc:7"), 1.0);
+        assert_eq!(value(&registry, "cc:1:7"), 0.0);
+        assert_eq!(value(&registry, "cc:2:8"), 0.0);
+
+        // Any channel follows the latest message, other channels keep their values.
+        controllers.midi([0xB0, 7, 0]);
+        assert_eq!(value(&registry, "cc:7"), 0.0);
+        assert_eq!(value(&registry, "cc:1:7"), 0.0);
+        assert_eq!(value(&registry, "cc:2:7"), 1.0);
+
+        controllers.midi([0xEF, 0x00, 0x00]);
+        assert_eq!(value(&registry, "bend:16"), -1.0);
+        assert_eq!(value(&registry, "bend"), -1.0);
+        assert_eq!(value(&registry, "bend:1"), 0.0);
+
+        // Other messages are ignored.
+        controllers.midi([0x90, 7, 100]);
+        assert_eq!(value(&registry, "cc:7"), 0.0);
+    }
+
+    #[test]
+    fn smoothing() {
+        let (controllers, registry) = setup();
+        controllers.midi([0xB0, 1, 127]);
+        let mut node = registry
+            .construct("cc:1:1:0.001", 1, 48_000, 0)
+            .unwrap()
+            .unwrap();
+        // Starts from the current value.
+        assert_eq!(node.output()[0], 1.0);
+        controllers.midi([0xB0, 1, 0]);
+        node.sample(&[]);
+        let x = node.output()[0];
+        assert!(x > 0.9 && x < 1.0, "{}", x);
+        // Time constant is 48 frames.
+        for _ in 0..47 {
+            node.sample(&[]);
+        }
+        assert!((node.output()[0] - (-1.0 as Sample).exp()).abs() < 1e-9);
+    }
+}
@@ -1,45 +1,267 @@
 use crate::context::Context;
+use crate::controllers::Controllers;
+use crate::patch::Patch;
+use crate::state::State;
+use crate::transport::Transport;
+use crate::voices::Voices;
+use audio_graph::graph::BLOCK;
+use audio_graph::handoff;
 use audio_graph::prelude::*;
+use audio_graph::registry::{Inputs, ModuleRegistry};
 use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::f64::consts::FRAC_PI_2;
 use std::sync::Arc;
-use vst::plugin::{Info, Plugin};
+use vst::host::Host;
+use vst::plugin::{CanDo, HostCallback, Info, Plugin};
 
 mod context;
+mod controllers;
 #[macro_use]
 mod macros;
+mod patch;
+mod state;
+mod transport;
 mod ui;
+mod voices;
 
 const CHANNELS: usize = 2;
 const PARAMETERS: usize = 16;
+/// Index of the host parameter which controls crossfade time, it goes right after `param:<N>`.
+const CROSSFADE: i32 = PARAMETERS as i32;
+/// Crossfade time in seconds when its parameter is at maximum.
+const MAX_CROSSFADE: Sample = 5.0;
+/// Number of external inputs of graphs: audio channels, `param:<N>`, per-voice and transport
+/// sources.
+const INPUTS: usize = CHANNELS + PARAMETERS + voices::CONTROLS + transport::SOURCES;
+/// MIDI events which don't fit are dropped to avoid allocations on the audio thread.
+const MAX_EVENTS: usize = 1024;
+
+/// Replace built-in `param:<N>` with one which only reaches host parameters: graphs' parameters
+/// also include per-voice and transport sources, which are read by their own modules.
+fn register_parameters(r: &mut ModuleRegistry) {
+    r.register(
+        &["param"],
+        Inputs::Ports(&[]),
+        "host parameter by index, e.g. param:0",
+        |c, _, _, args| match *args {
+            [index] => match index.parse::<usize>() {
+                Ok(index) if index < PARAMETERS => Ok(Box::new(Parameter::new(c, index))),
+                _ => Err(format!(
+                    "invalid parameter index `{}`, expected 0 to {}",
+                    index,
+                    PARAMETERS - 1
+                )),
+            },
+            _ => Err("param takes parameter index".into()),
+        },
+    );
+}
 
 struct SoundGarden {
+    host: HostCallback,
     context: Arc<Mutex<Context>>,
     editor: ui::Editor,
-    graph: Arc<Mutex<AudioGraph>>,
-    input: Vec<Sample>,
+    /// Patches compiled outside of the editor, e.g. when state is loaded, are sent via it.
+    /// Ref `recompile`.
+    sender: handoff::Sender<Patch>,
+    patch: Box<Patch>,
+    /// New patches compiled by the editor arrive here.
+    receiver: handoff::Receiver<Patch>,
+    /// Previous patch which is still playing while the new one fades in.
+    fading: Option<Box<Patch>>,
+    /// Crossfade progress in frames.
+    fade_position: usize,
+    fade_length: usize,
+    /// Whether the old and the new patches have the same structure, then they play correlated
+    /// signals and are crossfaded with equal gain rather than equal power, which would boost
+    /// them by 3 dB in the middle.
+    fade_linear: bool,
+    /// Crossfade time in seconds.
+    crossfade: Sample,
+    /// Copy of `Context::sample_rate` to avoid locking context on the audio thread.
+    sample_rate: usize,
+    /// Planar buffers of the current block: audio inputs followed by parameters, per-voice and
+    /// transport sources.
+    input: Vec<Vec<Sample>>,
+    output: Vec<Vec<Sample>>,
+    /// Output of the fading patch.
+    fade_output: Vec<Vec<Sample>>,
+    /// Output of a single voice, ref `Patch::render`.
+    voice_output: Vec<Vec<Sample>>,
     parameters: Vec<f64>,
+    /// MIDI events of the current host buffer with their offsets in frames.
+    events: VecDeque<(usize, [u8; 3])>,
+    voices: Voices,
+    /// Shared with controller sources of compiled programs.
+    controllers: Arc<Controllers>,
+    transport: Transport,
 }
 
 impl Default for SoundGarden {
     fn default() -> Self {
+        let mut registry = ModuleRegistry::default();
+        register_parameters(&mut registry);
+        voices::register(&mut registry);
+        transport::register(&mut registry);
+        let controllers = Arc::new(Controllers::default());
+        controllers::register(&mut registry, &controllers);
         let context = Arc::new(Mutex::new(Context {
             channels: CHANNELS,
             sample_rate: 48_000,
-            parameters: PARAMETERS,
+            parameters: INPUTS - CHANNELS,
+            registry,
+            text: String::new(),
+            error: None,
+            reload: false,
         }));
-        let graph = Arc::new(Mutex::new(AudioGraph::new(CHANNELS, PARAMETERS)));
-        let editor = ui::Editor::new(context.clone(), graph.clone());
+        let patch = Box::new(Patch::new(Vec::new()));
+        let (sender, receiver) = handoff::channel();
+        let editor = ui::Editor::new(context.clone(), sender.clone());
         SoundGarden {
+            host: HostCallback::default(),
             context,
             editor,
-            graph,
-            input: vec![0.0; CHANNELS + PARAMETERS],
+            sender,
+            patch,
+            receiver,
+            fading: None,
+            fade_position: 0,
+            fade_length: 0,
+            fade_linear: false,
+            crossfade: 0.05,
+            sample_rate: 48_000,
+            input: vec![vec![0.0; BLOCK]; INPUTS],
+            output: vec![vec![0.0; BLOCK]; CHANNELS],
+            fade_output: vec![vec![0.0; BLOCK]; CHANNELS],
+            voice_output: vec![vec![0.0; BLOCK]; CHANNELS],
             parameters: vec![0.0; PARAMETERS],
+            events: VecDeque::with_capacity(MAX_EVENTS),
+            voices: Voices::default(),
+            controllers,
+            transport: Transport::default(),
         }
     }
 }
 
+impl SoundGarden {
+    /// Switch to the patch sent by the editor, if any, and start crossfading to it.
+    /// It must be real-time safe, that's why the old patch is not dropped here but retired to be
+    /// destroyed by the editor thread.
+    fn update_patch(&mut self) {
+        if self.fade_position >= self.fade_length {
+            if let Some(old) = self.fading.take() {
+                // Retired slot might be still occupied, then try again on the next block.
+                if let Err(old) = self.receiver.retire(old) {
+                    self.fading = Some(old);
+                }
+            }
+        }
+        // New patch waits until the current crossfade is over to avoid jumps in the old patches'
+        // gain.
+        if self.fading.is_some() {
+            return;
+        }
+        if let Some(mut patch) = self.receiver.receive() {
+            // Nodes which didn't change continue from where the old patch is.
+            self.fade_linear = patch.transplant(&mut self.patch);
+            self.fading = Some(std::mem::replace(&mut self.patch, patch));
+            self.fade_position = 0;
+            self.fade_length = (self.crossfade * self.sample_rate as Sample) as usize;
+        }
+    }
+
+    /// Compile the program stored in the context and send it to the audio thread. If it fails,
+    /// the current patch keeps playing and the error is stored in the context for the editor.
+    /// It is done here and not in the editor when settings change, as the editor might be not open.
+    fn recompile(&self, context: &mut Context) {
+        match context.compile(&context.text) {
+            Ok(patch) => {
+                context.error = None;
+                self.sender.send(Box::new(patch));
+            }
+            Err(e) => context.error = Some(e.to_string()),
+        }
+    }
+
+    /// Apply MIDI events which are due at `offset` and return how many frames to process until
+    /// the next event, up to BLOCK and the end of host buffer of `samples` frames.
+    fn next_block(&mut self, offset: usize, samples: usize) -> usize {
+        let frames = BLOCK.min(samples - offset);
+        while let Some(&(time, data)) = self.events.front() {
+            if time > offset {
+                return frames.min(time - offset);
+            }
+            self.midi(data);
+            self.events.pop_front();
+        }
+        frames
+    }
+
+    /// Apply MIDI events which are left after the end of host buffer.
+    fn flush_events(&mut self) {
+        while let Some((_, data)) = self.events.pop_front() {
+            self.midi(data);
+        }
+    }
+
+    fn midi(&mut self, data: [u8; 3]) {
+        self.voices.midi(data);
+        self.controllers.midi(data);
+    }
+
+    /// Compute the next `frames` frames (up to BLOCK) into `output`, crossfading between the old
+    /// and the new patches if there is one in progress.
+    fn process_block(&mut self, frames: usize) {
+        self.transport.render(
+            &mut self.input[(INPUTS - transport::SOURCES)..],
+            self.sample_rate,
+            frames,
+        );
+        self.patch.render(
+            &self.voices,
+            &mut self.input,
+            &mut self.output,
+            &mut self.voice_output,
+            frames,
+        );
+        if let Some(old) = &mut self.fading {
+            if self.fade_position < self.fade_length {
+                old.render(
+                    &self.voices,
+                    &mut self.input,
+                    &mut self.fade_output,
+                    &mut self.voice_output,
+                    frames,
+                );
+                for frame in 0..frames {
+                    let x = self.fade_position as Sample / self.fade_length as Sample;
+                    let (fade_in, fade_out) = if self.fade_linear {
+                        (x, 1.0 - x)
+                    } else {
+                        ((FRAC_PI_2 * x).sin(), (FRAC_PI_2 * x).cos())
+                    };
+                    for (y, z) in self.output.iter_mut().zip(&self.fade_output) {
+                        y[frame] = fade_in * y[frame] + fade_out * z[frame];
+                    }
+                    if self.fade_position < self.fade_length {
+                        self.fade_position += 1;
+                    }
+                }
+            }
+        }
+        self.voices.rendered();
+    }
+}
+
 impl Plugin for SoundGarden {
+    fn new(host: HostCallback) -> Self {
+        SoundGarden {
+            host,
+            ..Default::default()
+        }
+    }
+
     fn get_info(&self) -> Info {
         Info {
             name: "Sound Garden".to_string(),
@@ -48,19 +270,86 @@ impl Plugin for SoundGarden {
             inputs: CHANNELS as i32,
             outputs: CHANNELS as i32,
             f64_precision: true,
-            parameters: PARAMETERS as i32, // param:<N>
+            parameters: PARAMETERS as i32 + 1, // param:<N> and crossfade
             version: 1,
             category: vst::plugin::Category::Synth,
+            preset_chunks: true,
             ..Default::default()
         }
     }
 
+    fn can_do(&self, can_do: CanDo) -> vst::api::Supported {
+        match can_do {
+            CanDo::ReceiveMidiEvent => vst::api::Supported::Yes,
+            _ => vst::api::Supported::Maybe,
+        }
+    }
+
+    fn process_events(&mut self, events: &vst::api::Events) {
+        for event in events.events() {
+            if let vst::event::Event::Midi(event) = event {
+                if self.events.len() < MAX_EVENTS {
+                    self.events
+                        .push_back((event.delta_frames.max(0) as usize, event.data));
+                }
+            }
+        }
+    }
+
     fn get_editor(&mut self) -> Option<&mut vst::editor::Editor> {
         Some(&mut self.editor)
     }
 
     fn set_sample_rate(&mut self, rate: f32) {
-        self.context.lock().sample_rate = rate as usize;
+        if self.sample_rate == rate as usize {
+            return;
+        }
+        self.sample_rate = rate as usize;
+        let mut context = self.context.lock();
+        context.sample_rate = self.sample_rate;
+        // Modules compute their coefficients from the sample rate they are built with.
+        self.recompile(&mut context);
+    }
+
+    fn get_preset_data(&mut self) -> Vec<u8> {
+        State {
+            parameters: self.parameters.clone(),
+            crossfade: self.crossfade,
+            text: self.context.lock().text.clone(),
+        }
+        .to_bytes()
+    }
+
+    fn get_bank_data(&mut self) -> Vec<u8> {
+        // There is a single program, so bank is the same as preset.
+        self.get_preset_data()
+    }
+
+    fn load_preset_data(&mut self, data: &[u8]) {
+        let state = match State::from_bytes(data) {
+            Some(state) => state,
+            None => {
+                println!("Failed to load plugin state");
+                return;
+            }
+        };
+        for (x, y) in self.parameters.iter_mut().zip(&state.parameters) {
+            *x = *y;
+        }
+        // Treat NaN as no crossfade, `clamp` would keep it.
+        self.crossfade = if state.crossfade.is_nan() {
+            0.0
+        } else {
+            state.crossfade.clamp(0.0, MAX_CROSSFADE)
+        };
+        let mut context = self.context.lock();
+        context.text = state.text;
+        context.reload = true;
+        self.recompile(&mut context);
+    }
+
+    fn load_bank_data(&mut self, data: &[u8]) {
+        self.load_preset_data(data);
     }
 
     fn can_be_automated(&self, _index: i32) -> bool {
@@ -70,6 +359,8 @@ impl Plugin for SoundGarden {
     fn get_parameter(&self, index: i32) -> f32 {
         if index < PARAMETERS as i32 {
             self.parameters[index as usize] as f32
+        } else if index == CROSSFADE {
+            (self.crossfade / MAX_CROSSFADE) as f32
         } else {
             0.0
         }
@@ -78,57 +369,107 @@ impl Plugin for SoundGarden {
     fn set_parameter(&mut self, index: i32, value: f32) {
         if index < PARAMETERS as i32 {
             self.parameters[index as usize] = Sample::from(value);
+        } else if index == CROSSFADE {
+            self.crossfade = Sample::from(value) * MAX_CROSSFADE;
         }
     }
 
-    fn process(&mut self, buffer: &mut vst::buffer::AudioBuffer<f32>) {
-        let (inputs, mut outputs) = buffer.split();
+    fn get_parameter_name(&self, index: i32) -> String {
+        if index == CROSSFADE {
+            "Crossfade".to_string()
+        } else {
+            format!("param:{}", index)
+        }
+    }
+
+    fn get_parameter_text(&self, index: i32) -> String {
+        if index == CROSSFADE {
+            format!("{:.3}", self.crossfade)
+        } else {
+            format!("{:.3}", self.get_parameter(index))
+        }
+    }
 
-        // Iterate over inputs as (&f32, &f32)
-        let (left, right) = inputs.split_at(1);
-        let stereo_in = left[0].iter().zip(right[0].iter());
+    fn get_parameter_label(&self, index: i32) -> String {
+        if index == CROSSFADE {
+            "s".to_string()
+        } else {
+            "".to_string()
+        }
+    }
 
-        // Iterate over outputs as (&mut f32, &mut f32)
-        let (mut left, mut right) = outputs.split_at_mut(1);
-        let stereo_out = left[0].iter_mut().zip(right[0].iter_mut());
+    fn process(&mut self, buffer: &mut vst::buffer::AudioBuffer<f32>) {
+        let (inputs, mut outputs) = buffer.split();
+        let (left_in, right_in) = inputs.split_at(1);
+        let (mut left_out, mut right_out) = outputs.split_at_mut(1);
+        let (left_in, right_in) = (&left_in[0], &right_in[0]);
+        let (left_out, right_out) = (&mut left_out[0], &mut right_out[0]);
 
-        // Prepare parameters and graph
-        self.input[CHANNELS..].clone_from_slice(&self.parameters);
-        let mut g = self.graph.lock();
+        // Prepare parameters and patch
+        for (buffer, value) in self.input[CHANNELS..].iter_mut().zip(&self.parameters) {
+            for x in buffer.iter_mut() {
+                *x = *value;
+            }
+        }
+        self.update_patch();
+        self.transport
+            .update(self.host.get_time_info(Transport::mask()));
 
-        // Zip and process
-        for ((left_in, right_in), (left_out, right_out)) in stereo_in.zip(stereo_out) {
-            self.input[0] = Sample::from(*left_in);
-            self.input[1] = Sample::from(*right_in);
-            let output = g.sample(&self.input);
-            *left_out = output[0] as f32;
-            *right_out = output[1] as f32;
+        // Process host buffer in blocks split at MIDI events
+        let samples = left_in.len();
+        let mut offset = 0;
+        while offset < samples {
+            let frames = self.next_block(offset, samples);
+            let range = offset..(offset + frames);
+            for (x, y) in self.input[0].iter_mut().zip(&left_in[range.clone()]) {
+                *x = Sample::from(*y);
+            }
+            for (x, y) in self.input[1].iter_mut().zip(&right_in[range.clone()]) {
+                *x = Sample::from(*y);
+            }
+            self.process_block(frames);
+            for (y, x) in left_out[range.clone()].iter_mut().zip(&self.output[0]) {
+                *y = *x as f32;
+            }
+            for (y, x) in right_out[range].iter_mut().zip(&self.output[1]) {
+                *y = *x as f32;
+            }
+            offset += frames;
         }
+        self.flush_events();
     }
 
     fn process_f64(&mut self, buffer: &mut vst::buffer::AudioBuffer<f64>) {
         let (inputs, mut outputs) = buffer.split();
+        let (left_in, right_in) = inputs.split_at(1);
+        let (mut left_out, mut right_out) = outputs.split_at_mut(1);
+        let (left_in, right_in) = (&left_in[0], &right_in[0]);
+        let (left_out, right_out) = (&mut left_out[0], &mut right_out[0]);
 
-        // Iterate over inputs as (&f32, &f32)
-        let (left, right) = inputs.split_at(1);
-        let stereo_in = left[0].iter().zip(right[0].iter());
-
-        // Iterate over outputs as (&mut f32, &mut f32)
-        let (mut left, mut right) = outputs.split_at_mut(1);
-        let stereo_out = left[0].iter_mut().zip(right[0].iter_mut());
-
-        // Prepare parameters and graph
-        self.input[CHANNELS..].clone_from_slice(&self.parameters);
-        let mut g = self.graph.lock();
+        // Prepare parameters and patch
+        for (buffer, value) in self.input[CHANNELS..].iter_mut().zip(&self.parameters) {
+            for x in buffer.iter_mut() {
+                *x = *value;
+            }
+        }
+        self.update_patch();
+        self.transport
+            .update(self.host.get_time_info(Transport::mask()));
 
-        // Zip and process
-        for ((left_in, right_in), (left_out, right_out)) in stereo_in.zip(stereo_out) {
-            self.input[0] = *left_in;
-            self.input[1] = *right_in;
-            let output = g.sample(&self.input);
-            *left_out = output[0];
-            *right_out = output[1];
+        // Process host buffer in blocks split at MIDI events
+        let samples = left_in.len();
+        let mut offset = 0;
+        while offset < samples {
+            let frames = self.next_block(offset, samples);
+            let range = offset..(offset + frames);
+            self.input[0][..frames].clone_from_slice(&left_in[range.clone()]);
+            self.input[1][..frames].clone_from_slice(&right_in[range.clone()]);
+            self.process_block(frames);
+            left_out[range.clone()].clone_from_slice(&self.output[0][..frames]);
+            right_out[range].clone_from_slice(&self.output[1][..frames]);
+            offset += frames;
         }
+        self.flush_events();
     }
 }
 
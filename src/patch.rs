@@ -0,0 +1,133 @@
+use crate::voices::{Voices, CONTROLS, GATE};
+use crate::{CHANNELS, INPUTS, PARAMETERS};
+use audio_graph::prelude::*;
+
+/// Compiled program: a graph per voice, or a single graph if program is not polyphonic.
+pub struct Patch {
+    graphs: Vec<AudioGraph>,
+}
+
+impl Patch {
+    pub fn new(graphs: Vec<AudioGraph>) -> Self {
+        Patch { graphs }
+    }
+
+    /// Take over state of the `previous` patch, voice by voice, and return whether all voices
+    /// kept their structure. Ref `AudioGraph::transplant`.
+    pub fn transplant(&mut self, previous: &mut Patch) -> bool {
+        let mut same = self.graphs.len() == previous.graphs.len();
+        for (graph, previous) in self.graphs.iter_mut().zip(previous.graphs.iter_mut()) {
+            same &= graph.transplant(previous);
+        }
+        same
+    }
+
+    /// Compute the next `frames` frames (up to BLOCK) of all voices and sum them into `output`.
+    ///
+    /// `input` has a buffer per external input, per-voice sources are written into `CONTROLS`
+    /// of them right after parameters. Gate of a voice stolen since the last block is low for
+    /// the first frame. `scratch` must have the same shape as `output`.
+    pub fn render(
+        &mut self,
+        voices: &Voices,
+        input: &mut [Vec<Sample>],
+        output: &mut [Vec<Sample>],
+        scratch: &mut [Vec<Sample>],
+        frames: usize,
+    ) {
+        for output in output.iter_mut() {
+            for y in output[..frames].iter_mut() {
+                *y = 0.0;
+            }
+        }
+        let controls = CHANNELS + PARAMETERS;
+        for (graph, voice) in self.graphs.iter_mut().zip(voices.iter()) {
+            for (buffer, value) in input[controls..(controls + CONTROLS)]
+                .iter_mut()
+                .zip(&voice.controls())
+            {
+                for x in buffer[..frames].iter_mut() {
+                    *x = *value;
+                }
+            }
+            if voice.retrigger() && frames > 0 {
+                input[controls + GATE][0] = 0.0;
+            }
+            let mut slices: [&[Sample]; INPUTS] = [&[]; INPUTS];
+            for (x, buffer) in slices.iter_mut().zip(input.iter()) {
+                *x = &buffer[..frames];
+            }
+            let (left, right) = scratch.split_at_mut(1);
+            graph.process_block(
+                &slices,
+                &mut [&mut left[0][..frames], &mut right[0][..frames]],
+                frames,
+            );
+            for (output, voice_output) in output.iter_mut().zip(scratch.iter()) {
+                for (y, x) in output[..frames].iter_mut().zip(&voice_output[..frames]) {
+                    *y += *x;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voices::{self, VOICES};
+    use audio_graph::graph::BLOCK;
+    use audio_graph::lang;
+    use audio_graph::registry::ModuleRegistry;
+
+    fn patch(text: &str, voices: usize) -> Patch {
+        let mut registry = ModuleRegistry::default();
+        voices::register(&mut registry);
+        let graphs = (0..voices)
+            .map(|_| {
+                lang::compile_with(&registry, text, CHANNELS, 48_000, INPUTS - CHANNELS).unwrap()
+            })
+            .collect();
+        Patch::new(graphs)
+    }
+
+    /// Render a block and return its left channel.
+    fn render(patch: &mut Patch, voices: &Voices, frames: usize) -> Vec<Sample> {
+        let mut input = vec![vec![0.0; BLOCK]; INPUTS];
+        let mut output = vec![vec![0.0; BLOCK]; CHANNELS];
+        let mut scratch = vec![vec![0.0; BLOCK]; CHANNELS];
+        patch.render(voices, &mut input, &mut output, &mut scratch, frames);
+        assert_eq!(output[0][..frames], output[1][..frames]);
+        output[0][..frames].to_vec()
+    }
+
+    #[test]
+    fn voices_are_summed() {
+        let mut patch = patch("note", VOICES);
+        let mut voices = Voices::default();
+        voices.midi([0x90, 60, 100]);
+        voices.midi([0x90, 40, 100]);
+        assert_eq!(render(&mut patch, &voices, 3), vec![100.0; 3]);
+    }
+
+    #[test]
+    fn monophonic_patch_plays_the_first_voice() {
+        let mut patch = patch("param:0 1 +", 1);
+        let voices = Voices::default();
+        assert_eq!(render(&mut patch, &voices, 2), vec![1.0; 2]);
+    }
+
+    #[test]
+    fn stolen_voice_gate_falls_for_a_frame() {
+        let mut patch = patch("gate", VOICES);
+        let mut voices = Voices::default();
+        for note in 60..(60 + VOICES as u8) {
+            voices.midi([0x90, note, 100]);
+        }
+        assert_eq!(render(&mut patch, &voices, 2), vec![8.0; 2]);
+        voices.midi([0x90, 80, 100]);
+        assert_eq!(render(&mut patch, &voices, 3), vec![7.0, 8.0, 8.0]);
+        voices.rendered();
+        assert_eq!(render(&mut patch, &voices, 2), vec![8.0; 2]);
+    }
+}
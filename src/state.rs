@@ -0,0 +1,127 @@
+//! Plugin state stored by the host in the project (VST chunk).
+//!
+//! Layout, all numbers are little-endian:
+//! * magic `SGVS`;
+//! * format version, u32;
+//! * number of parameters, u32, followed by their values, f64 each;
+//! * crossfade time in seconds, f64;
+//! * program text length in bytes, u32, followed by the UTF-8 text.
+use std::convert::TryInto;
+
+const MAGIC: &[u8; 4] = b"SGVS";
+const VERSION: u32 = 1;
+
+pub struct State {
+    pub parameters: Vec<f64>,
+    pub crossfade: f64,
+    pub text: String,
+}
+
+impl State {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&(self.parameters.len() as u32).to_le_bytes());
+        for x in &self.parameters {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+        data.extend_from_slice(&self.crossfade.to_le_bytes());
+        data.extend_from_slice(&(self.text.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.text.as_bytes());
+        data
+    }
+
+    /// Parse state, returning `None` if data is truncated, malformed or has unknown version.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+            return None;
+        }
+        let n = reader.u32()? as usize;
+        let parameters = (0..n).map(|_| reader.f64()).collect::<Option<Vec<_>>>()?;
+        let crossfade = reader.f64()?;
+        let len = reader.u32()? as usize;
+        let text = String::from_utf8(reader.take(len)?.to_vec()).ok()?;
+        Some(State {
+            parameters,
+            crossfade,
+            text,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> State {
+        State {
+            parameters: vec![0.0, 0.25, 1.0],
+            crossfade: 0.5,
+            text: "440 s 0.5 * ∿".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = state().to_bytes();
+        let loaded = State::from_bytes(&data).unwrap();
+        assert_eq!(loaded.parameters, state().parameters);
+        assert_eq!(loaded.crossfade, state().crossfade);
+        assert_eq!(loaded.text, state().text);
+    }
+
+    #[test]
+    fn truncated_data_is_rejected() {
+        let data = state().to_bytes();
+        for len in 0..data.len() {
+            assert!(State::from_bytes(&data[..len]).is_none(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let mut data = state().to_bytes();
+        data[0] = b'X';
+        assert!(State::from_bytes(&data).is_none());
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut data = state().to_bytes();
+        data[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(State::from_bytes(&data).is_none());
+    }
+
+    #[test]
+    fn invalid_text_is_rejected() {
+        let mut data = state().to_bytes();
+        let last = data.len() - 1;
+        data[last] = 0xff;
+        assert!(State::from_bytes(&data).is_none());
+    }
+}
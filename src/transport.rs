@@ -0,0 +1,236 @@
+//! Host transport and tempo.
+//!
+//! Host reports its position once per buffer, and it is extrapolated per frame using tempo.
+//! Sources are implemented as parameters which take slots right after per-voice sources:
+//! * `bpm` — tempo in beats per minute;
+//! * `beat` — position in quarter notes from the start of the project;
+//! * `bar` — position in quarter notes from the start of the current bar;
+//! * `playing` — 1 while transport is playing, 0 otherwise.
+//!
+//! `beat` stands still while transport is stopped, so `beat <division> bm` triggers on the host
+//! grid only during playback.
+use crate::voices::CONTROLS;
+use crate::PARAMETERS;
+use audio_graph::prelude::*;
+use audio_graph::registry::{Inputs, ModuleRegistry};
+use vst::api::{TimeInfo, TimeInfoFlags};
+
+/// Number of transport sources.
+pub const SOURCES: usize = 4;
+/// First slot of transport sources among parameters.
+const OFFSET: usize = PARAMETERS + CONTROLS;
+
+/// Register transport sources.
+pub fn register(r: &mut ModuleRegistry) {
+    r.register(
+        &["bpm"],
+        Inputs::Ports(&[]),
+        "host tempo in beats per minute",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, OFFSET))),
+    );
+    r.register(
+        &["beat"],
+        Inputs::Ports(&[]),
+        "host position in quarter notes",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, OFFSET + 1))),
+    );
+    r.register(
+        &["bar"],
+        Inputs::Ports(&[]),
+        "host position in quarter notes from the start of the bar",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, OFFSET + 2))),
+    );
+    r.register(
+        &["playing"],
+        Inputs::Ports(&[]),
+        "1 while host transport is playing, 0 otherwise",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, OFFSET + 3))),
+    );
+}
+
+/// Transport state as of the next frame to process.
+pub struct Transport {
+    tempo: Sample,
+    beat: Sample,
+    bar_start: Sample,
+    /// Length of bar in quarter notes.
+    bar_length: Sample,
+    playing: bool,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport {
+            tempo: 120.0,
+            beat: 0.0,
+            bar_start: 0.0,
+            bar_length: 4.0,
+            playing: false,
+        }
+    }
+}
+
+impl Transport {
+    /// Flags to request from the host in `get_time_info`.
+    pub fn mask() -> i32 {
+        (TimeInfoFlags::TEMPO_VALID
+            | TimeInfoFlags::PPQ_POS_VALID
+            | TimeInfoFlags::BARS_VALID
+            | TimeInfoFlags::TIME_SIG_VALID)
+            .bits()
+    }
+
+    /// Take position reported by the host at the start of its buffer. Values which host didn't
+    /// provide keep being extrapolated from the previous ones.
+    pub fn update(&mut self, info: Option<TimeInfo>) {
+        let info = match info {
+            Some(info) => info,
+            None => return,
+        };
+        let flags = TimeInfoFlags::from_bits_truncate(info.flags);
+        self.playing = flags.contains(TimeInfoFlags::TRANSPORT_PLAYING);
+        if flags.contains(TimeInfoFlags::TEMPO_VALID) && info.tempo > 0.0 {
+            self.tempo = info.tempo;
+        }
+        if flags.contains(TimeInfoFlags::PPQ_POS_VALID) {
+            self.beat = info.ppq_pos;
+        }
+        if flags.contains(TimeInfoFlags::BARS_VALID) {
+            self.bar_start = info.bar_start_pos;
+        }
+        if flags.contains(TimeInfoFlags::TIME_SIG_VALID)
+            && info.time_sig_numerator > 0
+            && info.time_sig_denominator > 0
+        {
+            self.bar_length =
+                Sample::from(4 * info.time_sig_numerator) / Sample::from(info.time_sig_denominator);
+        }
+    }
+
+    /// Write the next `frames` frames of sources into `buffers` and advance position.
+    pub fn render(&mut self, buffers: &mut [Vec<Sample>], sample_rate: usize, frames: usize) {
+        let step = if self.playing {
+            self.tempo / 60.0 / sample_rate as Sample
+        } else {
+            0.0
+        };
+        let playing = if self.playing { 1.0 } else { 0.0 };
+        for x in buffers[0][..frames].iter_mut() {
+            *x = self.tempo;
+        }
+        for (i, x) in buffers[1][..frames].iter_mut().enumerate() {
+            *x = self.beat + step * i as Sample;
+        }
+        for (i, x) in buffers[2][..frames].iter_mut().enumerate() {
+            // Bar start is updated once per host buffer, the position could cross a bar line
+            // in the meantime.
+            let beat = self.beat + step * i as Sample;
+            *x = (beat - self.bar_start).rem_euclid(self.bar_length);
+        }
+        for x in buffers[3][..frames].iter_mut() {
+            *x = playing;
+        }
+        self.beat += step * frames as Sample;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// At this rate and 120 BPM position advances by a quarter of a beat per frame.
+    const SAMPLE_RATE: usize = 8;
+    const FRAMES: usize = 4;
+
+    fn info(flags: TimeInfoFlags, tempo: Sample, ppq_pos: Sample) -> Option<TimeInfo> {
+        Some(TimeInfo {
+            flags: flags.bits(),
+            tempo,
+            ppq_pos,
+            bar_start_pos: 0.0,
+            time_sig_numerator: 3,
+            time_sig_denominator: 4,
+            ..Default::default()
+        })
+    }
+
+    fn playing() -> TimeInfoFlags {
+        TimeInfoFlags::from_bits_truncate(Transport::mask()) | TimeInfoFlags::TRANSPORT_PLAYING
+    }
+
+    fn stopped() -> TimeInfoFlags {
+        TimeInfoFlags::from_bits_truncate(Transport::mask())
+    }
+
+    /// Render a block and return buffers of bpm, beat, bar and playing.
+    fn render(transport: &mut Transport) -> Vec<Vec<Sample>> {
+        let mut buffers = vec![vec![0.0; FRAMES]; SOURCES];
+        transport.render(&mut buffers, SAMPLE_RATE, FRAMES);
+        buffers
+    }
+
+    #[test]
+    fn stopped_by_default() {
+        let mut transport = Transport::default();
+        for _ in 0..2 {
+            let buffers = render(&mut transport);
+            assert_eq!(buffers[0], vec![120.0; FRAMES]);
+            assert_eq!(buffers[1], vec![0.0; FRAMES]);
+            assert_eq!(buffers[2], vec![0.0; FRAMES]);
+            assert_eq!(buffers[3], vec![0.0; FRAMES]);
+        }
+    }
+
+    #[test]
+    fn position_is_extrapolated_across_blocks() {
+        let mut transport = Transport::default();
+        transport.update(info(playing(), 120.0, 2.0));
+        let buffers = render(&mut transport);
+        assert_eq!(buffers[1], vec![2.0, 2.25, 2.5, 2.75]);
+        // Bar of 3/4 is 3 quarter notes long, position crosses the bar line.
+        assert_eq!(buffers[2], vec![2.0, 2.25, 2.5, 2.75]);
+        assert_eq!(buffers[3], vec![1.0; FRAMES]);
+
+        // Host doesn't report position for every buffer.
+        transport.update(None);
+        let buffers = render(&mut transport);
+        assert_eq!(buffers[1], vec![3.0, 3.25, 3.5, 3.75]);
+        assert_eq!(buffers[2], vec![0.0, 0.25, 0.5, 0.75]);
+    }
+
+    #[test]
+    fn tempo_change() {
+        let mut transport = Transport::default();
+        transport.update(info(playing(), 120.0, 0.0));
+        render(&mut transport);
+        // Host position takes over the extrapolated one.
+        transport.update(info(playing(), 60.0, 0.5));
+        let buffers = render(&mut transport);
+        assert_eq!(buffers[0], vec![60.0; FRAMES]);
+        assert_eq!(buffers[1], vec![0.5, 0.625, 0.75, 0.875]);
+
+        // Invalid tempo is ignored.
+        transport.update(info(playing(), 0.0, 1.0));
+        let buffers = render(&mut transport);
+        assert_eq!(buffers[0], vec![60.0; FRAMES]);
+        assert_eq!(buffers[1], vec![1.0, 1.125, 1.25, 1.375]);
+    }
+
+    #[test]
+    fn play_and_stop() {
+        let mut transport = Transport::default();
+        transport.update(info(playing(), 120.0, 0.0));
+        render(&mut transport);
+        transport.update(info(stopped(), 120.0, 1.0));
+        for _ in 0..2 {
+            let buffers = render(&mut transport);
+            assert_eq!(buffers[1], vec![1.0; FRAMES]);
+            assert_eq!(buffers[2], vec![1.0; FRAMES]);
+            assert_eq!(buffers[3], vec![0.0; FRAMES]);
+        }
+        transport.update(info(playing(), 120.0, 1.0));
+        let buffers = render(&mut transport);
+        assert_eq!(buffers[1], vec![1.0, 1.25, 1.5, 1.75]);
+        assert_eq!(buffers[3], vec![1.0; FRAMES]);
+    }
+}
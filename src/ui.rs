@@ -1,5 +1,7 @@
 use crate::context::Context;
-use audio_graph::prelude::*;
+use crate::patch::Patch;
+use audio_graph::handoff;
+use audio_graph::lang;
 use parking_lot::Mutex;
 use sciter::{self, make_args, Element};
 use std::os::raw::c_void;
@@ -10,28 +12,25 @@ const HTML: &[u8] = include_bytes!("./main.htm");
 
 pub struct Editor {
     context: Arc<Mutex<Context>>,
-    graph: Arc<Mutex<AudioGraph>>,
+    sender: handoff::Sender<Patch>,
     frame: Option<sciter::window::Window>,
     is_open: Arc<Mutex<bool>>,
-    text: Arc<Mutex<String>>,
 }
 
 impl Editor {
-    pub fn new(context: Arc<Mutex<Context>>, graph: Arc<Mutex<AudioGraph>>) -> Self {
+    pub fn new(context: Arc<Mutex<Context>>, sender: handoff::Sender<Patch>) -> Self {
         Editor {
             context,
-            graph,
+            sender,
             frame: None,
             is_open: Arc::new(Mutex::new(false)),
-            text: Arc::new(Mutex::new("".to_string())),
         }
     }
 }
 
 struct EventHandler {
     context: Arc<Mutex<Context>>,
-    graph: Arc<Mutex<AudioGraph>>,
-    text: Arc<Mutex<String>>,
+    sender: handoff::Sender<Patch>,
 }
 
 struct HostHandler {
@@ -56,163 +55,49 @@ fn set_editor_text(root: &Element, text: &str) {
     };
 }
 
+/// Show the program of the context and why it failed to compile, if it did.
+fn show_context(root: &Element, context: &Context) {
+    set_editor_text(root, &context.text);
+    report_error(root, context.error.as_deref().unwrap_or(""));
+}
+
 impl EventHandler {
     fn graph_text_change(&mut self, root: &Element, text: String) {
-        let context = self.context.lock();
-        let channels = context.channels;
-        let sample_rate = context.sample_rate;
-        let mut nodes = Vec::new();
-        let mut tokens = Vec::new();
-        let mut g = AudioGraph::new(channels, context.parameters);
-        for token in text.split_whitespace() {
-            let node: Option<Node> = match token {
-                "s" => Some(Box::new(Osc::new(channels, sample_rate, sine))),
-                "sine" => Some(Box::new(OscPhase::new(channels, sample_rate, sine))),
-                "t" => Some(Box::new(Osc::new(channels, sample_rate, triangle))),
-                "tri" => Some(Box::new(OscPhase::new(channels, sample_rate, triangle))),
-                "w" => Some(Box::new(Phasor::new(channels, sample_rate))),
-                "saw" => Some(Box::new(Phasor0::new(channels, sample_rate))),
-                "p" | "pulse" => Some(Box::new(Pulse::new(channels, sample_rate))),
-                "+" => Some(Box::new(Fn2::new(channels, add))),
-                "-" => Some(Box::new(Fn2::new(channels, sub))),
-                "*" => Some(Box::new(Fn2::new(channels, mul))),
-                "/" => Some(Box::new(Fn2::new(channels, div))),
-                "\\" => Some(Box::new(Fn1::new(channels, recip))),
-                "^" | "pow" => Some(Box::new(Fn2::new(channels, pow))),
-                "unit" => Some(Box::new(Fn1::new(channels, unit))),
-                "r" | "range" => Some(Box::new(Fn3::new(channels, range))),
-                "n" | "noise" => Some(Box::new(Noise::new(channels))),
-                "delay" => Some(Box::new(Delay::new(channels, sample_rate, 60.0))),
-                "fb" | "feedback" => Some(Box::new(Feedback::new(channels, sample_rate, 60.0))),
-                "lpf" => Some(Box::new(LPF::new(channels, sample_rate))),
-                "hpf" => Some(Box::new(HPF::new(channels, sample_rate))),
-                "l" | "bqlpf" => Some(Box::new(BiQuad::new(
-                    channels,
-                    sample_rate,
-                    make_lpf_coefficients,
-                ))),
-                "h" | "bqhpf" => Some(Box::new(BiQuad::new(
-                    channels,
-                    sample_rate,
-                    make_hpf_coefficients,
-                ))),
-                "m2f" | "midi2freq" => Some(Box::new(Fn1::new(channels, midi2freq))),
-                "round" => Some(Box::new(Fn1::new(channels, round))),
-                "quantize" => Some(Box::new(Fn2::new(channels, quantize))),
-                "sin" => Some(Box::new(Fn1::new(channels, sin))),
-                "cos" => Some(Box::new(Fn1::new(channels, cos))),
-                "pan" => Some(Box::new(Pan3::new(channels))),
-                "pan1" => Some(Box::new(Pan1::new(channels))),
-                "pan2" => Some(Box::new(Pan2::new(channels))),
-                "in" | "input" => Some(Box::new(Input::new(channels))),
-                "cheb2" => Some(Box::new(Fn1::new(channels, cheb2))),
-                "cheb3" => Some(Box::new(Fn1::new(channels, cheb3))),
-                "cheb4" => Some(Box::new(Fn1::new(channels, cheb4))),
-                "cheb5" => Some(Box::new(Fn1::new(channels, cheb5))),
-                "cheb6" => Some(Box::new(Fn1::new(channels, cheb6))),
-                "sh" | "sample&hold" => Some(Box::new(SampleAndHold::new(channels))),
-                "m" | "metro" => Some(Box::new(Metro::new(channels, sample_rate))),
-                "dm" | "dmetro" => Some(Box::new(DMetro::new(channels, sample_rate))),
-                "mh" | "metroHold" => Some(Box::new(MetroHold::new(channels, sample_rate))),
-                "dmh" | "dmetroHold" => Some(Box::new(DMetroHold::new(channels, sample_rate))),
-                "yin" | "pitch" => Some(Box::new(Yin::new(channels, sample_rate, 1024, 512, 0.2))),
-                "zip" => Some(Box::new(Zip::new(channels))),
-                _ => match token.parse::<Sample>() {
-                    Ok(x) => Some(Box::new(Constant::new(channels, x))),
-                    Err(_) => {
-                        let subcmd = token.split(':').collect::<Vec<_>>();
-                        match subcmd[0] {
-                            "param" => match subcmd.get(1) {
-                                Some(x) => match x.parse::<usize>() {
-                                    Ok(index) => Some(Box::new(Parameter::new(channels, index))),
-                                    Err(_) => None,
-                                },
-                                None => None,
-                            },
-                            _ => None,
-                        }
-                    }
-                },
-            };
-            nodes.push(node.and_then(|node| Some(g.add_node(node))));
-            tokens.push(token);
-        }
-        let mut stack = Vec::new();
-        for (i, (idx, token)) in nodes.into_iter().zip(&tokens).enumerate() {
-            match idx {
-                Some(idx) => {
-                    let inputs = g.node(idx).inputs();
-                    if stack.len() < (inputs as usize) {
-                        report_error(
-                            root,
-                            &format!(
-                                "Node #{} `{}` has not enough inputs on the stack.",
-                                i + 1,
-                                token
-                            ),
-                        );
-                        return;
-                    }
-                    let mut sources = Vec::new();
-                    for _ in 0..inputs {
-                        sources.push(stack.pop().unwrap());
-                    }
-                    g.set_sources_rev(idx, &sources);
-                    stack.push(idx);
-                }
-                None => match *token {
-                    "pop" => {
-                        if stack.is_empty() {
-                            report_error(root, &format!("Nothing to pop at #{}!", i + 1));
-                            return;
-                        }
-                        stack.pop();
-                    }
-                    "swap" => {
-                        let len = stack.len();
-                        if len < 2 {
-                            report_error(root, &format!("Nothing to swap at #{}!", i + 1));
-                            return;
-                        }
-                        stack.swap(len - 2, len - 1);
-                    }
-                    "dup" => match stack.last() {
-                        Some(idx) => {
-                            stack.push(*idx);
-                        }
-                        None => {
-                            report_error(root, &format!("Nothing to dup at #{}!", i + 1));
-                            return;
-                        }
-                    },
-                    "rot" => {
-                        let len = stack.len();
-                        if len < 3 {
-                            report_error(root, &format!("Nothing to rot at #{}!", i + 1));
-                            return;
-                        }
-                        stack.swap(len - 2, len - 1);
-                        stack.swap(len - 3, len - 1);
-                    }
-                    _ => {
-                        report_error(
-                            root,
-                            &format!("Node #{} `{}` is unknown module.", i + 1, token),
-                        );
-                        return;
-                    }
-                },
+        let mut context = self.context.lock();
+        match context.compile(&text) {
+            Ok(g) => {
+                report_error(root, "");
+                context.text = text;
+                context.error = None;
+                self.sender.send(Box::new(g));
             }
+            Err(e) => report_error(root, &e.to_string()),
         }
-        report_error(root, "");
-        *self.text.lock() = text;
-        *self.graph.lock() = g
+    }
+
+    fn help(&mut self, _root: &Element) -> String {
+        lang::help(&self.context.lock().registry)
+    }
+
+    fn module_names(&mut self, _root: &Element) -> String {
+        self.context.lock().registry.names().join(" ")
+    }
+
+    fn signature(&mut self, _root: &Element, token: String) -> String {
+        self.context
+            .lock()
+            .registry
+            .signature(&token)
+            .unwrap_or_default()
     }
 }
 
 impl sciter::EventHandler for EventHandler {
     dispatch_script_call! {
         fn graph_text_change(String);
+        fn help();
+        fn module_names();
+        fn signature(String);
     }
 }
 
@@ -244,8 +129,7 @@ impl vst::editor::Editor for Editor {
         );
         let event_handler = EventHandler {
             context: self.context.clone(),
-            graph: self.graph.clone(),
-            text: self.text.clone(),
+            sender: self.sender.clone(),
         };
         frame.event_handler(event_handler);
         let host_handler = HostHandler {
@@ -255,12 +139,33 @@ impl vst::editor::Editor for Editor {
         frame.load_html(HTML, None);
         frame.expand(false);
         if let Ok(root) = Element::from_window(frame.get_hwnd()) {
-            set_editor_text(&root, &self.text.lock());
+            let mut context = self.context.lock();
+            show_context(&root, &context);
+            context.reload = false;
         }
         self.frame = Some(frame);
         *self.is_open.lock() = true;
     }
 
+    fn idle(&mut self) {
+        // Destroy graphs replaced by the audio thread.
+        self.sender.collect();
+        // Host might load state while the editor is open, then the text in it is stale and would
+        // overwrite the loaded program on the next commit.
+        if !self.is_open() {
+            return;
+        }
+        let mut context = self.context.lock();
+        if context.reload {
+            if let Some(frame) = &self.frame {
+                if let Ok(root) = Element::from_window(frame.get_hwnd()) {
+                    show_context(&root, &context);
+                    context.reload = false;
+                }
+            }
+        }
+    }
+
     fn is_open(&mut self) -> bool {
         *self.is_open.lock()
     }
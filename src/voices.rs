@@ -0,0 +1,229 @@
+//! MIDI notes and voice allocation.
+//!
+//! Programs which use any of per-voice sources (`freq`, `gate`, `velocity`, `note`) are compiled
+//! once per voice, and incoming notes are distributed among voices. Sources are implemented as
+//! parameters which take slots right after host parameters, and each voice's graph gets its own
+//! values in these slots.
+use crate::PARAMETERS;
+use audio_graph::lang;
+use audio_graph::prelude::*;
+use audio_graph::registry::{Inputs, ModuleRegistry};
+
+/// Number of voices of polyphonic program.
+pub const VOICES: usize = 8;
+/// Number of per-voice sources, ref `Voice::controls`.
+pub const CONTROLS: usize = 4;
+/// Position of `gate` among per-voice sources.
+pub const GATE: usize = 1;
+const WORDS: [&str; CONTROLS] = ["freq", "gate", "velocity", "note"];
+
+/// Register per-voice sources.
+pub fn register(r: &mut ModuleRegistry) {
+    r.register(
+        &["freq"],
+        Inputs::Ports(&[]),
+        "frequency of the voice's note",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, PARAMETERS))),
+    );
+    r.register(
+        &["gate"],
+        Inputs::Ports(&[]),
+        "1 while the voice's note is held, 0 after it is released",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, PARAMETERS + 1))),
+    );
+    r.register(
+        &["velocity"],
+        Inputs::Ports(&[]),
+        "velocity of the voice's note, 0..1",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, PARAMETERS + 2))),
+    );
+    r.register(
+        &["note"],
+        Inputs::Ports(&[]),
+        "MIDI pitch of the voice's note",
+        |c, _, _, _| Ok(Box::new(Parameter::new(c, PARAMETERS + 3))),
+    );
+}
+
+/// Whether program has to be compiled once per voice.
+pub fn is_polyphonic(text: &str) -> bool {
+    lang::tokenize(text)
+        .iter()
+        .any(|(_, token)| WORDS.contains(token))
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Voice {
+    note: u8,
+    velocity: Sample,
+    gate: bool,
+    /// Voice was stolen while its note was held. Gate has to be low for the first frame of the
+    /// next block, otherwise gate-driven envelopes don't see the new note.
+    retrigger: bool,
+    /// When the note was pressed or released, in allocator's events.
+    age: u64,
+}
+
+impl Voice {
+    /// Values of per-voice sources in the order of their slots.
+    pub fn controls(&self) -> [Sample; CONTROLS] {
+        let note = Sample::from(self.note);
+        [
+            midi2freq(note),
+            if self.gate { 1.0 } else { 0.0 },
+            self.velocity,
+            note,
+        ]
+    }
+
+    /// Whether gate has to be low for the first frame of the block, ref `Voices::rendered`.
+    pub fn retrigger(&self) -> bool {
+        self.retrigger
+    }
+}
+
+/// Voice allocator.
+#[derive(Default)]
+pub struct Voices {
+    voices: [Voice; VOICES],
+    counter: u64,
+}
+
+impl Voices {
+    pub fn iter(&self) -> impl Iterator<Item = &Voice> {
+        self.voices.iter()
+    }
+
+    /// Forget retriggers of stolen voices once the block they start at is rendered.
+    pub fn rendered(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.retrigger = false;
+        }
+    }
+
+    /// Handle MIDI message, ignoring everything except notes.
+    pub fn midi(&mut self, data: [u8; 3]) {
+        match data[0] & 0xF0 {
+            0x90 if data[2] > 0 => self.note_on(data[1], data[2]),
+            0x80 | 0x90 => self.note_off(data[1]),
+            // All notes off.
+            0xB0 if data[1] == 123 => {
+                self.counter += 1;
+                for voice in self.voices.iter_mut().filter(|voice| voice.gate) {
+                    voice.gate = false;
+                    voice.age = self.counter;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn note_on(&mut self, note: u8, velocity: u8) {
+        self.counter += 1;
+        // Take the voice which was released the longest time ago. If all voices are held, steal
+        // the oldest one.
+        let voice = self
+            .voices
+            .iter_mut()
+            .min_by_key(|voice| (voice.gate, voice.age))
+            .unwrap();
+        *voice = Voice {
+            note,
+            velocity: Sample::from(velocity) / 127.0,
+            gate: true,
+            retrigger: voice.gate,
+            age: self.counter,
+        };
+    }
+
+    fn note_off(&mut self, note: u8) {
+        self.counter += 1;
+        for voice in self.voices.iter_mut() {
+            if voice.gate && voice.note == note {
+                voice.gate = false;
+                voice.age = self.counter;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(voices: &mut Voices, note: u8) {
+        voices.midi([0x90, note, 127]);
+    }
+
+    fn note_off(voices: &mut Voices, note: u8) {
+        voices.midi([0x80, note, 0]);
+    }
+
+    /// Note of each voice, if it is held.
+    fn held(voices: &Voices) -> Vec<Option<u8>> {
+        voices
+            .iter()
+            .map(|voice| if voice.gate { Some(voice.note) } else { None })
+            .collect()
+    }
+
+    #[test]
+    fn notes_take_free_voices() {
+        let mut voices = Voices::default();
+        note_on(&mut voices, 60);
+        voices.midi([0x90, 64, 64]);
+        let mut expected = vec![None; VOICES];
+        expected[0] = Some(60);
+        expected[1] = Some(64);
+        assert_eq!(held(&voices), expected);
+        let controls = voices.iter().nth(1).unwrap().controls();
+        assert_eq!(controls[GATE], 1.0);
+        assert!((controls[2] - 64.0 / 127.0).abs() < 1e-9);
+        assert_eq!(controls[3], 64.0);
+        assert!(voices.iter().all(|voice| !voice.retrigger()));
+    }
+
+    #[test]
+    fn released_voices_are_reused_oldest_first() {
+        let mut voices = Voices::default();
+        for note in 60..(60 + VOICES as u8) {
+            note_on(&mut voices, note);
+        }
+        note_off(&mut voices, 62);
+        // Note on with zero velocity is note off.
+        voices.midi([0x90, 61, 0]);
+        let voice = voices.iter().nth(2).unwrap();
+        assert_eq!(voice.controls()[GATE], 0.0);
+        // Released voice keeps its note for release stage of envelopes.
+        assert_eq!(voice.controls()[3], 62.0);
+
+        note_on(&mut voices, 70);
+        note_on(&mut voices, 71);
+        let held = held(&voices);
+        assert_eq!(held[2], Some(70));
+        assert_eq!(held[1], Some(71));
+        assert!(voices.iter().all(|voice| !voice.retrigger()));
+
+        voices.midi([0xB0, 123, 0]);
+        assert!(voices.iter().all(|voice| !voice.gate));
+    }
+
+    #[test]
+    fn oldest_held_voice_is_stolen_and_retriggered() {
+        let mut voices = Voices::default();
+        for note in 60..(60 + VOICES as u8) {
+            note_on(&mut voices, note);
+        }
+        note_on(&mut voices, 80);
+        let voice = voices.iter().next().unwrap();
+        assert_eq!(voice.note, 80);
+        assert!(voice.gate);
+        assert!(voice.retrigger());
+        // The stolen note doesn't hang on after its note off.
+        note_off(&mut voices, 60);
+        assert!(voices.iter().next().unwrap().gate);
+
+        voices.rendered();
+        assert!(voices.iter().all(|voice| !voice.retrigger()));
+    }
+}